@@ -0,0 +1,117 @@
+//! Compares point lookups and full scans of a read-only database served by redb's `FileBackend`,
+//! by `SharedFileBackend` (what read-only file opens use) and by `MmapBackend`. The redb page
+//! cache is disabled so every read reaches the backend.
+//!
+//! Run with `cargo bench --bench read`.
+
+// the library is only built as a C library, so the backends are compiled in directly
+#[path = "../src/backends/mmap.rs"]
+mod mmap;
+// the benchmark never seals the overlay
+#[allow(dead_code)]
+#[path = "../src/backends/read_only.rs"]
+mod read_only;
+#[path = "../src/backends/shared_file.rs"]
+mod shared_file;
+
+use mmap::MmapBackend;
+use read_only::ReadOnlyBackend;
+use redb::{
+    ReadableDatabase, ReadableTable, StorageBackend, TableDefinition, backends::FileBackend,
+};
+use shared_file::SharedFileBackend;
+use std::{
+    fs::File,
+    hint::black_box,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("bench");
+const ENTRIES: u64 = 100_000;
+const LOOKUPS: u64 = 200_000;
+const VALUE_LEN: usize = 128;
+
+fn populate(path: &Path) {
+    let db = redb::Database::create(path).unwrap();
+    let tx = db.begin_write().unwrap();
+    {
+        let mut table = tx.open_table(TABLE).unwrap();
+        let value = [0x5a; VALUE_LEN];
+        for i in 0..ENTRIES {
+            table.insert(i, value.as_slice()).unwrap();
+        }
+    }
+    tx.commit().unwrap();
+}
+
+fn open(backend: impl StorageBackend) -> redb::Database {
+    redb::Database::builder()
+        .set_cache_size(0)
+        .create_with_backend(ReadOnlyBackend::new(backend).unwrap())
+        .unwrap()
+}
+
+fn random_lookups(db: &redb::Database) -> Duration {
+    let tx = db.begin_read().unwrap();
+    let table = tx.open_table(TABLE).unwrap();
+
+    // xorshift, so every backend sees the same key sequence
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let value = table.get(state % ENTRIES).unwrap().unwrap();
+        black_box(value.value());
+    }
+    start.elapsed()
+}
+
+fn full_scan(db: &redb::Database) -> Duration {
+    let tx = db.begin_read().unwrap();
+    let table = tx.open_table(TABLE).unwrap();
+
+    let start = Instant::now();
+    let mut count = 0;
+    for entry in table.iter().unwrap() {
+        let (key, value) = entry.unwrap();
+        black_box((key.value(), value.value()));
+        count += 1;
+    }
+    assert_eq!(count, ENTRIES);
+    start.elapsed()
+}
+
+fn report(name: &str, db: redb::Database) {
+    // warm the OS page cache so every backend reads from memory
+    full_scan(&db);
+
+    let lookups = random_lookups(&db);
+    let scan = full_scan(&db);
+    println!(
+        "{name:<17} {:>6.0} ns/lookup, {:>6.1} ms/scan",
+        lookups.as_nanos() as f64 / LOOKUPS as f64,
+        scan.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("redb-ffi-bench-{}.redb", std::process::id()));
+    populate(&path);
+
+    println!("{ENTRIES} entries, {VALUE_LEN} byte values, page cache disabled");
+
+    let file = File::options().read(true).open(&path).unwrap();
+    report("FileBackend", open(FileBackend::new(file).unwrap()));
+    let file = File::open(&path).unwrap();
+    report(
+        "SharedFileBackend",
+        open(SharedFileBackend::new(file).unwrap()),
+    );
+    let file = File::open(&path).unwrap();
+    report("MmapBackend", open(MmapBackend::new(file).unwrap()));
+
+    let _ = std::fs::remove_file(path);
+}
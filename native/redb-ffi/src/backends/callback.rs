@@ -0,0 +1,187 @@
+use crate::{REDB_OK, redb_storage_backend};
+use redb::StorageBackend;
+use std::{fmt, io};
+
+/// Forwards every storage operation to host callbacks.
+pub(crate) struct CallbackBackend {
+    vtable: redb_storage_backend,
+}
+
+// the host is responsible for making its callbacks safe to call from any thread
+unsafe impl Send for CallbackBackend {}
+unsafe impl Sync for CallbackBackend {}
+
+impl CallbackBackend {
+    pub(crate) fn new(vtable: redb_storage_backend) -> Self {
+        Self { vtable }
+    }
+}
+
+impl fmt::Debug for CallbackBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackBackend")
+            .field("user_data", &self.vtable.user_data)
+            .finish()
+    }
+}
+
+fn check(code: i32, operation: &str) -> io::Result<()> {
+    if code == REDB_OK {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "storage callback `{operation}` failed with code {code}"
+        )))
+    }
+}
+
+impl StorageBackend for CallbackBackend {
+    fn len(&self) -> io::Result<u64> {
+        let mut len = 0;
+        check((self.vtable.len)(self.vtable.user_data, &mut len), "len")?;
+        Ok(len)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        check(
+            (self.vtable.read)(self.vtable.user_data, offset, out.as_mut_ptr(), out.len()),
+            "read",
+        )
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        check((self.vtable.set_len)(self.vtable.user_data, len), "set_len")
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        check((self.vtable.sync_data)(self.vtable.user_data), "sync_data")
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        check(
+            (self.vtable.write)(self.vtable.user_data, offset, data.as_ptr(), data.len()),
+            "write",
+        )
+    }
+
+    fn close(&self) -> io::Result<()> {
+        match self.vtable.close {
+            Some(close) => check(close(self.vtable.user_data), "close"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::{
+        ptr,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    #[derive(Default)]
+    struct Storage {
+        data: Mutex<Vec<u8>>,
+        failing: AtomicBool,
+    }
+
+    fn storage<'a>(user_data: *mut c_void) -> &'a Storage {
+        unsafe { &*user_data.cast::<Storage>() }
+    }
+
+    fn result(storage: &Storage) -> i32 {
+        if storage.failing.load(Ordering::Relaxed) {
+            REDB_ERROR_DISK_FULL
+        } else {
+            REDB_OK
+        }
+    }
+
+    extern "C" fn len(user_data: *mut c_void, out_len: *mut u64) -> i32 {
+        let storage = storage(user_data);
+        unsafe { *out_len = storage.data.lock().unwrap().len() as u64 };
+        result(storage)
+    }
+
+    extern "C" fn read(user_data: *mut c_void, offset: u64, out: *mut u8, len: usize) -> i32 {
+        let storage = storage(user_data);
+        let data = storage.data.lock().unwrap();
+        let offset = offset as usize;
+        unsafe { ptr::copy_nonoverlapping(data[offset..offset + len].as_ptr(), out, len) };
+        result(storage)
+    }
+
+    extern "C" fn write(user_data: *mut c_void, offset: u64, data: *const u8, len: usize) -> i32 {
+        let storage = storage(user_data);
+        if storage.failing.load(Ordering::Relaxed) {
+            return REDB_ERROR_DISK_FULL;
+        }
+        let offset = offset as usize;
+        let source = unsafe { std::slice::from_raw_parts(data, len) };
+        storage.data.lock().unwrap()[offset..offset + len].copy_from_slice(source);
+        REDB_OK
+    }
+
+    extern "C" fn set_len(user_data: *mut c_void, len: u64) -> i32 {
+        let storage = storage(user_data);
+        if storage.failing.load(Ordering::Relaxed) {
+            return REDB_ERROR_DISK_FULL;
+        }
+        storage.data.lock().unwrap().resize(len as usize, 0);
+        REDB_OK
+    }
+
+    extern "C" fn sync_data(user_data: *mut c_void) -> i32 {
+        result(storage(user_data))
+    }
+
+    fn create(storage: &Storage) -> (i32, *mut c_void) {
+        let vtable = redb_storage_backend {
+            user_data: ptr::from_ref(storage).cast_mut().cast(),
+            len,
+            read,
+            write,
+            set_len,
+            sync_data,
+            close: None,
+        };
+        let options = redb_database_options {
+            cache_size: 1 << 20,
+            backend: redb_backend::Callback,
+            storage: &vtable,
+            encryption_key: ptr::null(),
+            read_only: false,
+            lock_timeout_ms: 0,
+        };
+        let mut db = ptr::null_mut();
+        let code = redb_create_database(c"unused".as_ptr(), &options, &mut db);
+        (code, db)
+    }
+
+    #[test]
+    fn reports_callback_failures_as_storage_errors() {
+        let storage = Storage::default();
+        storage.failing.store(true, Ordering::Relaxed);
+        assert_eq!(create(&storage).0, REDB_ERROR_STORAGE_ERROR);
+
+        storage.failing.store(false, Ordering::Relaxed);
+        let (code, db) = create(&storage);
+        assert_eq!(code, REDB_OK);
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        assert_eq!(
+            redb_insert(table, b"k".as_ptr(), 1, b"v".as_ptr(), 1),
+            REDB_OK
+        );
+        redb_free_table(table);
+
+        storage.failing.store(true, Ordering::Relaxed);
+        assert_eq!(redb_write_tx_commit(tx), REDB_ERROR_STORAGE_ERROR);
+        redb_free_database(db);
+    }
+}
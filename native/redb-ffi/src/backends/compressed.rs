@@ -0,0 +1,660 @@
+use redb::StorageBackend;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, io,
+    sync::RwLock,
+};
+
+// Physical layout:
+//
+//   header slot := magic:[u8; 8] version:u32 codec:u8 reserved:[u8; 3] sequence:u64 len:u64
+//                  index_offset:u64 index_len:u64 checksum:u64
+//   index       := extent*
+//   extent      := offset:u64 len:u32
+//
+// The file starts with two header slots. Each sync writes the next sequence number to the other
+// slot, so a torn header write leaves the previous one intact. The logical file is split into
+// blocks that are compressed independently and stored anywhere after the header slots; the index
+// maps each block to its extent. A zero length extent is a block of zeros and a `BLOCK_SIZE`
+// extent holds the block uncompressed.
+//
+// Blocks are never overwritten in place. Extents referenced by the last synced index are only
+// reused after the next sync has made the new index durable, which keeps the file consistent with
+// whatever redb last synced.
+
+const MAGIC: &[u8; 8] = b"REDBLZ4\0";
+const VERSION: u32 = 1;
+const CODEC_LZ4: u8 = 1;
+
+const BLOCK_SIZE: usize = 16 * 1024;
+const HEADER_SLOT_SIZE: u64 = 512;
+const HEADER_LEN: usize = 8 + 4 + 4 + 8 + 8 + 8 + 8 + 8;
+const DATA_START: u64 = 2 * HEADER_SLOT_SIZE;
+const INDEX_ENTRY_SIZE: usize = 8 + 4;
+// extents are rounded up to limit fragmentation of the free space
+const ALLOCATION_UNIT: u64 = 64;
+
+#[derive(Clone, Copy, Default)]
+struct Extent {
+    offset: u64,
+    len: u32,
+}
+
+#[derive(Default)]
+struct FreeSpace {
+    by_offset: BTreeMap<u64, u64>,
+    by_size: BTreeSet<(u64, u64)>,
+    // end of the allocated area; everything after it is free
+    end: u64,
+}
+
+impl FreeSpace {
+    fn allocate(&mut self, len: u64) -> u64 {
+        let len = len.next_multiple_of(ALLOCATION_UNIT);
+        let Some(&(size, offset)) = self.by_size.range((len, 0)..).next() else {
+            let offset = self.end;
+            self.end += len;
+            return offset;
+        };
+
+        self.by_size.remove(&(size, offset));
+        self.by_offset.remove(&offset);
+        if size > len {
+            self.insert(offset + len, size - len);
+        }
+        offset
+    }
+
+    fn free(&mut self, mut offset: u64, len: u64) {
+        let mut len = len.next_multiple_of(ALLOCATION_UNIT);
+
+        if let Some((&prev_offset, &prev_len)) = self.by_offset.range(..offset).next_back()
+            && prev_offset + prev_len == offset
+        {
+            self.remove(prev_offset, prev_len);
+            offset = prev_offset;
+            len += prev_len;
+        }
+        if let Some(&next_len) = self.by_offset.get(&(offset + len)) {
+            self.remove(offset + len, next_len);
+            len += next_len;
+        }
+
+        if offset + len == self.end {
+            self.end = offset;
+        } else {
+            self.insert(offset, len);
+        }
+    }
+
+    /// Where the allocated area would end without `extent`, if it is the last one.
+    fn start_of(&self, extent: Extent) -> u64 {
+        let len = u64::from(extent.len).next_multiple_of(ALLOCATION_UNIT);
+        if extent.len == 0 || extent.offset + len != self.end {
+            return self.end;
+        }
+        match self.by_offset.range(..extent.offset).next_back() {
+            Some((&offset, &free)) if offset + free == extent.offset => offset,
+            _ => extent.offset,
+        }
+    }
+
+    fn insert(&mut self, offset: u64, len: u64) {
+        self.by_offset.insert(offset, len);
+        self.by_size.insert((len, offset));
+    }
+
+    fn remove(&mut self, offset: u64, len: u64) {
+        self.by_offset.remove(&offset);
+        self.by_size.remove(&(len, offset));
+    }
+}
+
+struct State {
+    len: u64,
+    blocks: Vec<Extent>,
+    index: Extent,
+    sequence: u64,
+    free: FreeSpace,
+    // extents that are still referenced by the last synced index
+    pending_free: Vec<Extent>,
+    // extents allocated since the last sync, which can be reused immediately
+    fresh: HashSet<u64>,
+    physical_len: u64,
+    // set when the index differs from the last synced one
+    dirty: bool,
+}
+
+impl State {
+    fn release(&mut self, extent: Extent) {
+        if extent.len == 0 {
+            return;
+        }
+        if self.fresh.remove(&extent.offset) {
+            self.free.free(extent.offset, u64::from(extent.len));
+        } else {
+            self.pending_free.push(extent);
+        }
+    }
+
+    /// The file space past `end`.
+    fn trailing_free(&self, end: u64) -> u64 {
+        self.physical_len - end.max(DATA_START).min(self.physical_len)
+    }
+
+    fn allocate(&mut self, len: usize) -> Extent {
+        let offset = self.free.allocate(len as u64);
+        self.fresh.insert(offset);
+        Extent {
+            offset,
+            len: len as u32,
+        }
+    }
+}
+
+/// Compresses fixed-size blocks of an inner backend with LZ4.
+pub(crate) struct CompressedBackend<B: StorageBackend> {
+    inner: B,
+    state: RwLock<State>,
+}
+
+impl<B: StorageBackend> fmt::Debug for CompressedBackend<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedBackend")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// FNV-1a
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+struct Header {
+    sequence: u64,
+    len: u64,
+    index: Extent,
+}
+
+fn encode_header(header: &Header) -> [u8; HEADER_LEN] {
+    let mut buf = [0; HEADER_LEN];
+    buf[..8].copy_from_slice(MAGIC);
+    buf[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    buf[12] = CODEC_LZ4;
+    buf[16..24].copy_from_slice(&header.sequence.to_le_bytes());
+    buf[24..32].copy_from_slice(&header.len.to_le_bytes());
+    buf[32..40].copy_from_slice(&header.index.offset.to_le_bytes());
+    buf[40..48].copy_from_slice(&u64::from(header.index.len).to_le_bytes());
+    let checksum = checksum(&buf[..48]);
+    buf[48..].copy_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+fn decode_header(buf: &[u8; HEADER_LEN]) -> Option<Header> {
+    let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+
+    if &buf[..8] != MAGIC
+        || buf[8..12] != VERSION.to_le_bytes()
+        || buf[12] != CODEC_LZ4
+        || u64_at(48) != checksum(&buf[..48])
+    {
+        return None;
+    }
+
+    Some(Header {
+        sequence: u64_at(16),
+        len: u64_at(24),
+        index: Extent {
+            offset: u64_at(32),
+            len: u32::try_from(u64_at(40)).ok()?,
+        },
+    })
+}
+
+impl<B: StorageBackend> CompressedBackend<B> {
+    pub(crate) fn new(inner: B) -> io::Result<Self> {
+        let physical_len = inner.len()?;
+        let mut state = State {
+            len: 0,
+            blocks: Vec::new(),
+            index: Extent::default(),
+            sequence: 0,
+            free: FreeSpace {
+                end: DATA_START,
+                ..Default::default()
+            },
+            pending_free: Vec::new(),
+            fresh: HashSet::new(),
+            physical_len,
+            dirty: false,
+        };
+
+        if physical_len == 0 {
+            let backend = Self {
+                inner,
+                state: RwLock::new(state),
+            };
+            backend.sync_data()?;
+            return Ok(backend);
+        }
+
+        let header = Self::read_latest_header(&inner, physical_len)?;
+        let mut index = vec![0; header.index.len as usize];
+        inner.read(header.index.offset, &mut index)?;
+
+        let count = header.len.div_ceil(BLOCK_SIZE as u64) as usize;
+        if index.len() != count * INDEX_ENTRY_SIZE {
+            return Err(invalid_data("compressed file index is corrupt"));
+        }
+        state.blocks = index
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|entry| Extent {
+                offset: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                len: u32::from_le_bytes(entry[8..].try_into().unwrap()),
+            })
+            .collect();
+        state.len = header.len;
+        state.index = header.index;
+        state.sequence = header.sequence;
+
+        // everything not referenced by the index is free
+        let mut used: Vec<_> = state
+            .blocks
+            .iter()
+            .chain([&state.index])
+            .filter(|extent| extent.len != 0)
+            .map(|extent| {
+                let len = u64::from(extent.len).next_multiple_of(ALLOCATION_UNIT);
+                (extent.offset, extent.offset + len)
+            })
+            .collect();
+        used.sort_unstable();
+        let mut end = DATA_START;
+        for (start, stop) in used {
+            if start < end {
+                return Err(invalid_data("compressed file index is corrupt"));
+            }
+            if start > end {
+                state.free.insert(end, start - end);
+            }
+            end = stop;
+        }
+        state.free.end = end;
+
+        Ok(Self {
+            inner,
+            state: RwLock::new(state),
+        })
+    }
+
+    fn read_latest_header(inner: &B, physical_len: u64) -> io::Result<Header> {
+        let mut latest: Option<Header> = None;
+        for slot in 0..2 {
+            let offset = slot * HEADER_SLOT_SIZE;
+            if offset + HEADER_LEN as u64 > physical_len {
+                continue;
+            }
+
+            let mut buf = [0; HEADER_LEN];
+            inner.read(offset, &mut buf)?;
+            if let Some(header) = decode_header(&buf)
+                && latest
+                    .as_ref()
+                    .is_none_or(|latest| header.sequence > latest.sequence)
+            {
+                latest = Some(header);
+            }
+        }
+
+        latest.ok_or_else(|| invalid_data("not a compressed redb file"))
+    }
+
+    fn read_block(&self, extent: Extent, out: &mut [u8; BLOCK_SIZE]) -> io::Result<()> {
+        match extent.len as usize {
+            0 => {
+                out.fill(0);
+                Ok(())
+            }
+            BLOCK_SIZE => self.inner.read(extent.offset, out),
+            len => {
+                let mut compressed = vec![0; len];
+                self.inner.read(extent.offset, &mut compressed)?;
+                match lz4_flex::block::decompress_into(&compressed, out) {
+                    Ok(BLOCK_SIZE) => Ok(()),
+                    _ => Err(invalid_data("compressed block is corrupt")),
+                }
+            }
+        }
+    }
+
+    fn write_block(
+        &self,
+        state: &mut State,
+        index: usize,
+        block: &[u8; BLOCK_SIZE],
+    ) -> io::Result<()> {
+        let extent = if block.iter().all(|&b| b == 0) {
+            Extent::default()
+        } else {
+            let compressed = lz4_flex::block::compress(block);
+            let data = if compressed.len() < BLOCK_SIZE {
+                compressed.as_slice()
+            } else {
+                block.as_slice()
+            };
+
+            let extent = state.allocate(data.len());
+            self.ensure_physical_len(state, extent.offset + data.len() as u64)?;
+            self.inner.write(extent.offset, data)?;
+            extent
+        };
+
+        let previous = std::mem::replace(&mut state.blocks[index], extent);
+        state.release(previous);
+        Ok(())
+    }
+
+    /// Writes the index and then the header that makes it current, after which the extents the
+    /// previous index referenced are free.
+    fn commit(&self, state: &mut State) -> io::Result<()> {
+        let mut index = Vec::with_capacity(state.blocks.len() * INDEX_ENTRY_SIZE);
+        for extent in &state.blocks {
+            index.extend_from_slice(&extent.offset.to_le_bytes());
+            index.extend_from_slice(&extent.len.to_le_bytes());
+        }
+        let index_len =
+            u32::try_from(index.len()).map_err(|_| invalid_data("compressed file is too large"))?;
+
+        let previous_index = state.index;
+        let index_extent = state.allocate(index_len as usize);
+        self.ensure_physical_len(state, index_extent.offset + u64::from(index_len))?;
+        self.inner.write(index_extent.offset, &index)?;
+        self.inner.sync_data()?;
+
+        let header = Header {
+            sequence: state.sequence + 1,
+            len: state.len,
+            index: index_extent,
+        };
+        let slot = header.sequence % 2;
+        self.inner
+            .write(slot * HEADER_SLOT_SIZE, &encode_header(&header))?;
+        self.inner.sync_data()?;
+
+        state.sequence = header.sequence;
+        state.index = index_extent;
+        state.fresh.clear();
+        state.dirty = false;
+        if previous_index.len != 0 {
+            state.pending_free.push(previous_index);
+        }
+        for extent in std::mem::take(&mut state.pending_free) {
+            state.free.free(extent.offset, u64::from(extent.len));
+        }
+
+        Ok(())
+    }
+
+    fn ensure_physical_len(&self, state: &mut State, len: u64) -> io::Result<()> {
+        if len > state.physical_len {
+            // grow in large steps to avoid resizing the file on every block
+            let target = len.max(state.physical_len + state.physical_len / 4);
+            self.inner.set_len(target)?;
+            state.physical_len = target;
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for CompressedBackend<B> {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.state.read().unwrap().len)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        let state = self.state.read().unwrap();
+        if offset + out.len() as u64 > state.len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        let mut position = offset;
+        let mut written = 0;
+        while written < out.len() {
+            let index = (position / BLOCK_SIZE as u64) as usize;
+            let start = (position % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - start).min(out.len() - written);
+
+            self.read_block(state.blocks[index], &mut block)?;
+            out[written..written + count].copy_from_slice(&block[start..start + count]);
+
+            position += count as u64;
+            written += count;
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&self, new_len: u64) -> io::Result<()> {
+        let mut state = self.state.write().unwrap();
+        let count = new_len.div_ceil(BLOCK_SIZE as u64) as usize;
+
+        // zero the tail of a partially kept block, so growing again exposes zeros
+        let tail = (new_len % BLOCK_SIZE as u64) as usize;
+        if new_len < state.len && tail != 0 {
+            let mut block = [0; BLOCK_SIZE];
+            self.read_block(state.blocks[count - 1], &mut block)?;
+            block[tail..].fill(0);
+            self.write_block(&mut state, count - 1, &block)?;
+        }
+
+        if count < state.blocks.len() {
+            for extent in state.blocks.split_off(count) {
+                state.release(extent);
+            }
+        } else {
+            state.blocks.resize(count, Extent::default());
+        }
+        state.len = new_len;
+        state.dirty = true;
+
+        Ok(())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        let mut state = self.state.write().unwrap();
+        self.commit(&mut state)?;
+
+        // the new index is written before the extents it replaces are freed, so it can end up
+        // alone at the tail; writing it once more moves it into the freed space
+        if state.trailing_free(state.free.end) <= state.physical_len / 4
+            && state.trailing_free(state.free.start_of(state.index)) > state.physical_len / 4
+        {
+            self.commit(&mut state)?;
+        }
+
+        // release trailing free space once it outweighs the growth headroom
+        if state.trailing_free(state.free.end) > state.physical_len / 4 {
+            let end = state.free.end.max(DATA_START);
+            self.inner.set_len(end)?;
+            state.physical_len = end;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut state = self.state.write().unwrap();
+        if offset + data.len() as u64 > state.len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        let mut position = offset;
+        let mut consumed = 0;
+        while consumed < data.len() {
+            let index = (position / BLOCK_SIZE as u64) as usize;
+            let start = (position % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - start).min(data.len() - consumed);
+
+            if count < BLOCK_SIZE {
+                self.read_block(state.blocks[index], &mut block)?;
+            }
+            block[start..start + count].copy_from_slice(&data[consumed..consumed + count]);
+            self.write_block(&mut state, index, &block)?;
+
+            position += count as u64;
+            consumed += count;
+        }
+        state.dirty = true;
+
+        Ok(())
+    }
+
+    fn close(&self) -> io::Result<()> {
+        // persist the index even if redb never synced, e.g. after non-durable commits
+        if self.state.read().unwrap().dirty {
+            self.sync_data()?;
+        }
+        self.inner.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::SharedMemoryBackend;
+
+    const LEN: u64 = 8 * BLOCK_SIZE as u64;
+
+    fn open(memory: &SharedMemoryBackend) -> CompressedBackend<SharedMemoryBackend> {
+        CompressedBackend::new(memory.clone()).unwrap()
+    }
+
+    fn read_vec(
+        backend: &CompressedBackend<SharedMemoryBackend>,
+        offset: u64,
+        len: usize,
+    ) -> Vec<u8> {
+        let mut data = vec![0; len];
+        backend.read(offset, &mut data).unwrap();
+        data
+    }
+
+    // compresses to the same size in every round
+    fn round_data(round: u64) -> Vec<u8> {
+        (0..LEN).map(|i| (i % 61 + round) as u8).collect()
+    }
+
+    #[test]
+    fn round_trips_through_reopen() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory);
+        backend.set_len(LEN).unwrap();
+        backend
+            .write(BLOCK_SIZE as u64 - 2, b"spans blocks")
+            .unwrap();
+        backend.write(LEN - 4, &[0xff; 4]).unwrap();
+        backend.close().unwrap();
+
+        let backend = open(&memory);
+        assert_eq!(backend.len().unwrap(), LEN);
+        assert_eq!(
+            read_vec(&backend, BLOCK_SIZE as u64 - 2, 12),
+            b"spans blocks"
+        );
+        assert_eq!(read_vec(&backend, LEN - 4, 4), [0xff; 4]);
+        assert_eq!(read_vec(&backend, 2 * BLOCK_SIZE as u64, 4), [0; 4]);
+    }
+
+    #[test]
+    fn reuses_freed_extents() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory);
+        backend.set_len(LEN).unwrap();
+
+        let mut settled = 0;
+        for round in 0..20 {
+            backend.write(0, &round_data(round)).unwrap();
+            backend.sync_data().unwrap();
+            // zeroed blocks free their extents as well
+            backend.write(0, &[0; 3 * BLOCK_SIZE]).unwrap();
+            backend.sync_data().unwrap();
+
+            if round < 5 {
+                settled = settled.max(memory.len().unwrap());
+            } else {
+                assert!(memory.len().unwrap() <= settled, "grew in round {round}");
+            }
+        }
+
+        let backend = open(&memory);
+        let mut expected = round_data(19);
+        expected[..3 * BLOCK_SIZE].fill(0);
+        assert_eq!(read_vec(&backend, 0, LEN as usize), expected);
+    }
+
+    #[test]
+    fn truncates_and_regrows_with_zeros() {
+        // incompressible, so every block takes a full extent
+        let mut seed = 1u64;
+        let data: Vec<u8> = (0..LEN)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect();
+
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory);
+        backend.set_len(LEN).unwrap();
+        backend.write(0, &data).unwrap();
+        backend.sync_data().unwrap();
+        let full = memory.len().unwrap();
+
+        backend.set_len(2 * BLOCK_SIZE as u64).unwrap();
+        backend.sync_data().unwrap();
+        assert!(memory.len().unwrap() < full / 2);
+
+        let kept = BLOCK_SIZE + 100;
+        backend.set_len(kept as u64).unwrap();
+        backend.close().unwrap();
+
+        let backend = open(&memory);
+        assert_eq!(backend.len().unwrap(), kept as u64);
+        assert_eq!(read_vec(&backend, 0, kept), data[..kept]);
+        backend.set_len(LEN).unwrap();
+        assert_eq!(read_vec(&backend, kept as u64, BLOCK_SIZE), [0; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn falls_back_to_the_previous_header() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory);
+        backend.set_len(100).unwrap();
+        backend.sync_data().unwrap();
+        backend.set_len(200).unwrap();
+        backend.sync_data().unwrap();
+        drop(backend);
+
+        // creating wrote sequence 1, so the last sync wrote sequence 3 to the second slot
+        let slot = HEADER_SLOT_SIZE;
+        let mut byte = [0];
+        memory.read(slot + 20, &mut byte).unwrap();
+        memory.write(slot + 20, &[byte[0] ^ 0xff]).unwrap();
+        assert_eq!(open(&memory).len().unwrap(), 100);
+
+        // a header only partly written before a crash
+        memory.write(slot + 24, &[0; HEADER_LEN - 24]).unwrap();
+        assert_eq!(open(&memory).len().unwrap(), 100);
+    }
+}
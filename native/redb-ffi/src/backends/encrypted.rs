@@ -0,0 +1,386 @@
+use chacha20poly1305::{
+    AeadCore, AeadInPlace, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{OsRng, Tag},
+};
+use redb::StorageBackend;
+use std::{fmt, io, sync::RwLock};
+
+// Physical layout:
+//
+//   header slot := magic:[u8; 8] version:u32 reserved:u32 nonce:[u8; 24] sealed:[u8; 16]
+//                  tag:[u8; 16]
+//   sealed      := sequence:u64 len:u64
+//   block       := nonce:[u8; 24] ciphertext:[u8; BLOCK_SIZE] tag:[u8; 16]
+//
+// The file starts with two header slots holding the logical length, encrypted, which doubles as
+// the key check. Each resize writes the next sequence number to the other slot and the newest slot
+// that decrypts wins, so a torn header write leaves the previous one intact. Each block is sealed
+// with a fresh random nonce and its index as associated data, so blocks cannot be swapped around.
+// A block whose nonce is all zeros has never been written and reads as zeros.
+
+const MAGIC: &[u8; 8] = b"REDBENC\0";
+const VERSION: u32 = 2;
+
+const BLOCK_SIZE: usize = 4096;
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+const STORED_BLOCK_SIZE: u64 = (NONCE_SIZE + BLOCK_SIZE + TAG_SIZE) as u64;
+const HEADER_SLOT_SIZE: u64 = (8 + 4 + 4 + NONCE_SIZE + 16 + TAG_SIZE) as u64;
+const DATA_START: u64 = 2 * HEADER_SLOT_SIZE;
+
+pub(crate) const ENCRYPTION_KEY_SIZE: usize = 32;
+
+#[derive(Debug)]
+pub(crate) enum EncryptionError {
+    Io(io::Error),
+    /// The file exists but was not written by `EncryptedBackend`
+    NotEncrypted,
+    /// Neither header slot could be decrypted with the supplied key
+    WrongKey,
+}
+
+impl From<io::Error> for EncryptionError {
+    fn from(err: io::Error) -> Self {
+        EncryptionError::Io(err)
+    }
+}
+
+/// Encrypts fixed-size blocks of an inner backend with XChaCha20-Poly1305.
+pub(crate) struct EncryptedBackend<B: StorageBackend> {
+    inner: B,
+    cipher: XChaCha20Poly1305,
+    // the write lock also serializes read-modify-write of partial blocks
+    header: RwLock<Header>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Header {
+    sequence: u64,
+    // logical length
+    len: u64,
+}
+
+impl<B: StorageBackend> fmt::Debug for EncryptedBackend<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedBackend")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn block_offset(index: u64) -> u64 {
+    DATA_START + index * STORED_BLOCK_SIZE
+}
+
+fn physical_len(len: u64) -> u64 {
+    block_offset(len.div_ceil(BLOCK_SIZE as u64))
+}
+
+impl<B: StorageBackend> EncryptedBackend<B> {
+    pub(crate) fn new(inner: B, key: &[u8; ENCRYPTION_KEY_SIZE]) -> Result<Self, EncryptionError> {
+        let backend = Self {
+            inner,
+            cipher: XChaCha20Poly1305::new(key.into()),
+            header: RwLock::new(Header::default()),
+        };
+
+        if backend.inner.len()? == 0 {
+            backend.inner.set_len(DATA_START)?;
+            backend.write_header(&Header::default())?;
+        } else {
+            let header = backend.read_latest_header()?;
+            *backend.header.write().unwrap() = header;
+        }
+
+        Ok(backend)
+    }
+
+    fn write_header(&self, header: &Header) -> io::Result<()> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = [0; 16];
+        sealed[..8].copy_from_slice(&header.sequence.to_le_bytes());
+        sealed[8..].copy_from_slice(&header.len.to_le_bytes());
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(&nonce, MAGIC, &mut sealed)
+            .map_err(|_| invalid_data("failed to encrypt header"))?;
+
+        let mut slot = Vec::with_capacity(HEADER_SLOT_SIZE as usize);
+        slot.extend_from_slice(MAGIC);
+        slot.extend_from_slice(&VERSION.to_le_bytes());
+        slot.extend_from_slice(&[0; 4]);
+        slot.extend_from_slice(&nonce);
+        slot.extend_from_slice(&sealed);
+        slot.extend_from_slice(&tag);
+        self.inner
+            .write(header.sequence % 2 * HEADER_SLOT_SIZE, &slot)
+    }
+
+    /// Decrypts the header slot at `offset`. `None` means the slot does not hold an encrypted
+    /// header at all, `Some(None)` that it could not be authenticated.
+    fn read_header(&self, offset: u64) -> io::Result<Option<Option<Header>>> {
+        let mut slot = [0; HEADER_SLOT_SIZE as usize];
+        self.inner.read(offset, &mut slot)?;
+        let (magic, rest) = slot.split_at(MAGIC.len());
+        let (version, rest) = rest.split_at(4);
+        let (nonce, rest) = rest[4..].split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(16);
+
+        if magic != MAGIC || version != VERSION.to_le_bytes() {
+            return Ok(None);
+        }
+
+        let mut sealed = [0; 16];
+        sealed.copy_from_slice(ciphertext);
+        let authenticated = self.cipher.decrypt_in_place_detached(
+            XNonce::from_slice(nonce),
+            MAGIC,
+            &mut sealed,
+            Tag::<XChaCha20Poly1305>::from_slice(tag),
+        );
+
+        Ok(Some(authenticated.ok().map(|()| Header {
+            sequence: u64::from_le_bytes(sealed[..8].try_into().unwrap()),
+            len: u64::from_le_bytes(sealed[8..].try_into().unwrap()),
+        })))
+    }
+
+    fn read_latest_header(&self) -> Result<Header, EncryptionError> {
+        let physical_len = self.inner.len()?;
+        let mut encrypted = false;
+        let mut latest: Option<Header> = None;
+        for slot in 0..2 {
+            let offset = slot * HEADER_SLOT_SIZE;
+            if offset + HEADER_SLOT_SIZE > physical_len {
+                continue;
+            }
+
+            let Some(header) = self.read_header(offset)? else {
+                continue;
+            };
+            encrypted = true;
+            if let Some(header) = header
+                && latest
+                    .as_ref()
+                    .is_none_or(|latest| header.sequence > latest.sequence)
+            {
+                latest = Some(header);
+            }
+        }
+
+        match latest {
+            Some(header) => Ok(header),
+            // a torn write only ever damages one slot, so a key that opens neither is wrong
+            None if encrypted => Err(EncryptionError::WrongKey),
+            None => Err(EncryptionError::NotEncrypted),
+        }
+    }
+
+    fn read_block(&self, index: u64, out: &mut [u8; BLOCK_SIZE]) -> io::Result<()> {
+        let mut stored = vec![0; STORED_BLOCK_SIZE as usize];
+        self.inner.read(block_offset(index), &mut stored)?;
+
+        let (nonce, rest) = stored.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(BLOCK_SIZE);
+        if nonce.iter().all(|&b| b == 0) {
+            out.fill(0);
+            return Ok(());
+        }
+
+        out.copy_from_slice(ciphertext);
+        self.cipher
+            .decrypt_in_place_detached(
+                XNonce::from_slice(nonce),
+                &index.to_le_bytes(),
+                out,
+                Tag::<XChaCha20Poly1305>::from_slice(tag),
+            )
+            // the header already proved the key, so this is damage rather than a wrong key
+            .map_err(|_| invalid_data("encrypted block failed authentication"))
+    }
+
+    fn write_block(&self, index: u64, block: &[u8; BLOCK_SIZE]) -> io::Result<()> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut stored = Vec::with_capacity(STORED_BLOCK_SIZE as usize);
+        stored.extend_from_slice(&nonce);
+        stored.extend_from_slice(block);
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(&nonce, &index.to_le_bytes(), &mut stored[NONCE_SIZE..])
+            .map_err(|_| invalid_data("failed to encrypt block"))?;
+        stored.extend_from_slice(&tag);
+
+        self.inner.write(block_offset(index), &stored)
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for EncryptedBackend<B> {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.header.read().unwrap().len)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        let len = self.header.read().unwrap().len;
+        if offset + out.len() as u64 > len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        let mut position = offset;
+        let mut written = 0;
+        while written < out.len() {
+            let index = position / BLOCK_SIZE as u64;
+            let start = (position % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - start).min(out.len() - written);
+
+            self.read_block(index, &mut block)?;
+            out[written..written + count].copy_from_slice(&block[start..start + count]);
+
+            position += count as u64;
+            written += count;
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&self, new_len: u64) -> io::Result<()> {
+        let mut header = self.header.write().unwrap();
+
+        // zero the tail of a partially kept block, so growing again exposes zeros
+        let tail = (new_len % BLOCK_SIZE as u64) as usize;
+        if new_len < header.len && tail != 0 {
+            let index = new_len / BLOCK_SIZE as u64;
+            let mut block = [0; BLOCK_SIZE];
+            self.read_block(index, &mut block)?;
+            block[tail..].fill(0);
+            self.write_block(index, &block)?;
+        }
+
+        let next = Header {
+            sequence: header.sequence + 1,
+            len: new_len,
+        };
+        self.inner.set_len(physical_len(new_len))?;
+        self.write_header(&next)?;
+        *header = next;
+
+        Ok(())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        self.inner.sync_data()
+    }
+
+    // held exclusively so concurrent partial block writes cannot lose each other's updates
+    #[allow(clippy::readonly_write_lock)]
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let header = self.header.write().unwrap();
+        if offset + data.len() as u64 > header.len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut block = [0; BLOCK_SIZE];
+        let mut position = offset;
+        let mut consumed = 0;
+        while consumed < data.len() {
+            let index = position / BLOCK_SIZE as u64;
+            let start = (position % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - start).min(data.len() - consumed);
+
+            if count < BLOCK_SIZE {
+                self.read_block(index, &mut block)?;
+            }
+            block[start..start + count].copy_from_slice(&data[consumed..consumed + count]);
+            self.write_block(index, &block)?;
+
+            position += count as u64;
+            consumed += count;
+        }
+
+        Ok(())
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.inner.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::SharedMemoryBackend;
+
+    const KEY: [u8; ENCRYPTION_KEY_SIZE] = [7; ENCRYPTION_KEY_SIZE];
+
+    fn open(
+        memory: &SharedMemoryBackend,
+    ) -> Result<EncryptedBackend<SharedMemoryBackend>, EncryptionError> {
+        EncryptedBackend::new(memory.clone(), &KEY)
+    }
+
+    fn flip_byte(memory: &SharedMemoryBackend, offset: u64) {
+        let mut byte = [0];
+        memory.read(offset, &mut byte).unwrap();
+        memory.write(offset, &[byte[0] ^ 0xff]).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_reopen() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory).unwrap();
+        backend.set_len(3 * BLOCK_SIZE as u64).unwrap();
+        backend
+            .write(BLOCK_SIZE as u64 - 2, b"spans blocks")
+            .unwrap();
+        backend.set_len(2 * BLOCK_SIZE as u64).unwrap();
+        drop(backend);
+
+        let backend = open(&memory).unwrap();
+        assert_eq!(backend.len().unwrap(), 2 * BLOCK_SIZE as u64);
+        let mut data = [0; 12];
+        backend.read(BLOCK_SIZE as u64 - 2, &mut data).unwrap();
+        assert_eq!(&data, b"spans blocks");
+    }
+
+    #[test]
+    fn rejects_a_wrong_key() {
+        let memory = SharedMemoryBackend::default();
+        open(&memory).unwrap().set_len(1).unwrap();
+
+        let result = EncryptedBackend::new(memory.clone(), &[8; ENCRYPTION_KEY_SIZE]);
+        assert!(matches!(result, Err(EncryptionError::WrongKey)));
+
+        let plain = SharedMemoryBackend::default();
+        plain.set_len(DATA_START).unwrap();
+        assert!(matches!(open(&plain), Err(EncryptionError::NotEncrypted)));
+    }
+
+    #[test]
+    fn falls_back_to_the_previous_header_after_a_torn_write() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory).unwrap();
+        backend.set_len(100).unwrap();
+        backend.set_len(200).unwrap();
+        drop(backend);
+
+        // the last resize wrote sequence 2, which lives in the first slot
+        flip_byte(&memory, HEADER_SLOT_SIZE - 1);
+        assert_eq!(open(&memory).unwrap().len().unwrap(), 100);
+    }
+
+    #[test]
+    fn reports_damaged_blocks_as_invalid_data() {
+        let memory = SharedMemoryBackend::default();
+        let backend = open(&memory).unwrap();
+        backend.set_len(BLOCK_SIZE as u64).unwrap();
+        backend.write(0, b"data").unwrap();
+        flip_byte(&memory, block_offset(0) + NONCE_SIZE as u64);
+
+        let err = backend.read(0, &mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,56 @@
+use redb::StorageBackend;
+use std::{
+    io,
+    sync::{Arc, RwLock},
+};
+
+/// In-memory storage whose contents stay reachable through a clone after the database that owns
+/// it has been dropped.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedMemoryBackend(Arc<RwLock<Vec<u8>>>);
+
+impl SharedMemoryBackend {
+    /// Takes the stored bytes, leaving the backend empty.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.write().unwrap())
+    }
+}
+
+fn out_of_range() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "index out of range")
+}
+
+impl StorageBackend for SharedMemoryBackend {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.0.read().unwrap().len() as u64)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        let data = self.0.read().unwrap();
+        let start = usize::try_from(offset).map_err(|_| out_of_range())?;
+        let src = data
+            .get(start..start + out.len())
+            .ok_or_else(out_of_range)?;
+        out.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        let len = usize::try_from(len).map_err(|_| out_of_range())?;
+        self.0.write().unwrap().resize(len, 0);
+        Ok(())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut dest = self.0.write().unwrap();
+        let start = usize::try_from(offset).map_err(|_| out_of_range())?;
+        dest.get_mut(start..start + data.len())
+            .ok_or_else(out_of_range)?
+            .copy_from_slice(data);
+        Ok(())
+    }
+}
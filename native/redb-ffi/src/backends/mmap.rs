@@ -0,0 +1,55 @@
+use memmap2::Mmap;
+use redb::StorageBackend;
+use std::{fs::File, io};
+
+/// Serves reads straight from a read-only memory mapping of the file.
+///
+/// The file is locked shared for the lifetime of the backend, so writers using redb's own locking
+/// cannot modify it underneath the mapping. Must be wrapped in `ReadOnlyBackend`.
+#[derive(Debug)]
+pub(crate) struct MmapBackend {
+    map: Mmap,
+    // keeps the shared lock alive
+    _file: File,
+}
+
+impl MmapBackend {
+    pub(crate) fn new(file: File) -> io::Result<Self> {
+        file.try_lock_shared().map_err(|err| match err {
+            std::fs::TryLockError::Error(err) => err,
+            std::fs::TryLockError::WouldBlock => io::ErrorKind::WouldBlock.into(),
+        })?;
+
+        // SAFETY: the shared lock keeps cooperating writers away while the file is mapped
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(Self { map, _file: file })
+    }
+}
+
+impl StorageBackend for MmapBackend {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.map.len() as u64)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        let data = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.map.get(start..start.checked_add(out.len())?))
+            .ok_or(io::ErrorKind::UnexpectedEof)?;
+        out.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn set_len(&self, _len: u64) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&self, _offset: u64, _data: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+}
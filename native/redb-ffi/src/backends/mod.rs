@@ -0,0 +1,86 @@
+//! `StorageBackend` implementations selectable through `redb_backend`.
+
+mod callback;
+mod compressed;
+mod encrypted;
+mod memory;
+mod mmap;
+mod read_only;
+mod shared_file;
+
+pub(crate) use callback::CallbackBackend;
+pub(crate) use compressed::CompressedBackend;
+pub(crate) use encrypted::{ENCRYPTION_KEY_SIZE, EncryptedBackend, EncryptionError};
+pub(crate) use memory::SharedMemoryBackend;
+pub(crate) use mmap::MmapBackend;
+pub(crate) use read_only::ReadOnlyBackend;
+pub(crate) use shared_file::SharedFileBackend;
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn open(path: &std::ffi::CStr, backend: redb_backend) -> *mut c_void {
+        let options = redb_database_options {
+            cache_size: 1 << 20,
+            backend,
+            storage: ptr::null(),
+            encryption_key: ptr::null(),
+            read_only: false,
+            lock_timeout_ms: 0,
+        };
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database(path.as_ptr(), &options, &mut db),
+            REDB_OK
+        );
+        db
+    }
+
+    #[test]
+    fn mmap_reads_what_a_file_database_wrote() {
+        let path = std::env::temp_dir().join(format!("redb-ffi-mmap-{}.redb", std::process::id()));
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        let value = [b'v'; 10_000];
+
+        let db = open(&c_path, redb_backend::File);
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        for key in 0u32..100 {
+            let key = key.to_be_bytes();
+            assert_eq!(
+                redb_insert(table, key.as_ptr(), key.len(), value.as_ptr(), value.len()),
+                REDB_OK
+            );
+        }
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+        redb_free_database(db);
+
+        let db = open(&c_path, redb_backend::Mmap);
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_read_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        for key in [0u32, 57, 99] {
+            let key = key.to_be_bytes();
+            let mut blob = redb_blob::EMPTY;
+            assert_eq!(redb_get(table, key.as_ptr(), key.len(), &mut blob), REDB_OK);
+            assert_eq!(unsafe { blob.into_vec() }, value);
+        }
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+
+        // the mapping is read-only
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_ERROR_READ_ONLY);
+        redb_free_database(db);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
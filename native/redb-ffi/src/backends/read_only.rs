@@ -0,0 +1,147 @@
+use redb::StorageBackend;
+use std::{
+    fmt, io,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Keeps the inner backend unmodified.
+///
+/// Opening a database always writes to it (redb marks the header as in use and may repair the
+/// allocator state), so writes are kept in memory until the database is open. Once `sealed` is set
+/// every further write is rejected.
+pub(crate) struct ReadOnlyBackend<B: StorageBackend> {
+    inner: B,
+    inner_len: u64,
+    overlay: RwLock<Overlay>,
+    sealed: Arc<AtomicBool>,
+}
+
+struct Overlay {
+    len: u64,
+    // bytes of `inner` beyond this offset were truncated away and read as zeros
+    inner_visible: u64,
+    // applied in order, so later writes win
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+impl<B: StorageBackend> fmt::Debug for ReadOnlyBackend<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadOnlyBackend")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: StorageBackend> ReadOnlyBackend<B> {
+    pub(crate) fn new(inner: B) -> io::Result<Self> {
+        let inner_len = inner.len()?;
+        Ok(Self {
+            inner,
+            inner_len,
+            overlay: RwLock::new(Overlay {
+                len: inner_len,
+                inner_visible: inner_len,
+                writes: Vec::new(),
+            }),
+            sealed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Returns the flag that rejects writes once set.
+    pub(crate) fn sealed(&self) -> Arc<AtomicBool> {
+        self.sealed.clone()
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.sealed.load(Ordering::Acquire) {
+            Err(io::Error::new(
+                io::ErrorKind::ReadOnlyFilesystem,
+                "database is opened read-only",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for ReadOnlyBackend<B> {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.overlay.read().unwrap().len)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        let overlay = self.overlay.read().unwrap();
+        let end = offset + out.len() as u64;
+        if end > overlay.len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let visible = overlay.inner_visible.min(self.inner_len);
+        if end <= visible {
+            self.inner.read(offset, out)?;
+        } else {
+            out.fill(0);
+            if offset < visible {
+                self.inner
+                    .read(offset, &mut out[..(visible - offset) as usize])?;
+            }
+        }
+
+        for (write_offset, data) in &overlay.writes {
+            let write_end = write_offset + data.len() as u64;
+            let start = offset.max(*write_offset);
+            let stop = end.min(write_end);
+            if start < stop {
+                out[(start - offset) as usize..(stop - offset) as usize].copy_from_slice(
+                    &data[(start - write_offset) as usize..(stop - write_offset) as usize],
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        self.check_writable()?;
+
+        let mut overlay = self.overlay.write().unwrap();
+        if len < overlay.len {
+            overlay.inner_visible = overlay.inner_visible.min(len);
+            overlay.writes.retain_mut(|(offset, data)| {
+                data.truncate(len.saturating_sub(*offset) as usize);
+                !data.is_empty()
+            });
+        }
+        overlay.len = len;
+
+        Ok(())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.check_writable()?;
+
+        let mut overlay = self.overlay.write().unwrap();
+        if offset + data.len() as u64 > overlay.len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        // redb rewrites the same header and pages repeatedly while opening
+        overlay
+            .writes
+            .retain(|(o, d)| *o != offset || d.len() > data.len());
+        overlay.writes.push((offset, data.to_vec()));
+
+        Ok(())
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.inner.close()
+    }
+}
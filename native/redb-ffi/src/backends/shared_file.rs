@@ -0,0 +1,69 @@
+use redb::StorageBackend;
+use std::{fs::File, io};
+
+/// Serves reads from the file with positional reads.
+///
+/// redb's `FileBackend` locks the file exclusively even when nothing is written, so this backend
+/// locks it shared instead: any number of read-only opens can use the file at once, while writers
+/// using redb's own locking stay out. Must be wrapped in `ReadOnlyBackend`.
+#[derive(Debug)]
+pub(crate) struct SharedFileBackend {
+    file: File,
+    // writers are locked out, so the length cannot change
+    len: u64,
+}
+
+impl SharedFileBackend {
+    pub(crate) fn new(file: File) -> io::Result<Self> {
+        file.try_lock_shared().map_err(|err| match err {
+            std::fs::TryLockError::Error(err) => err,
+            std::fs::TryLockError::WouldBlock => io::ErrorKind::WouldBlock.into(),
+        })?;
+
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, offset: u64, out: &mut [u8]) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, out, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut offset: u64, mut out: &mut [u8]) -> io::Result<()> {
+    while !out.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, out, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                out = &mut out[n..];
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+impl StorageBackend for SharedFileBackend {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.len)
+    }
+
+    fn read(&self, offset: u64, out: &mut [u8]) -> io::Result<()> {
+        read_exact_at(&self.file, offset, out)
+    }
+
+    fn set_len(&self, _len: u64) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&self, _offset: u64, _data: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+}
@@ -0,0 +1,251 @@
+//! Ownership of the buffers handed to the host as `redb_blob`s and `redb_u64_array`s.
+//!
+//! A buffer is a `Vec` taken apart into its raw parts, so it is released with exactly the element
+//! type, length and capacity it was allocated with.
+
+use crate::{redb_blob, redb_u64_array};
+use std::mem::ManuallyDrop;
+
+fn into_raw_parts<T>(values: Vec<T>) -> (*mut T, usize, usize) {
+    let mut values = ManuallyDrop::new(values);
+    (values.as_mut_ptr(), values.len(), values.capacity())
+}
+
+/// # Safety
+/// The parts must be null or come from `into_raw_parts` for the same `T`.
+unsafe fn from_raw_parts<T>(data: *mut T, len: usize, capacity: usize) -> Vec<T> {
+    if data.is_null() {
+        return Vec::new();
+    }
+    unsafe { Vec::from_raw_parts(data, len, capacity) }
+}
+
+impl redb_blob {
+    pub(crate) const EMPTY: redb_blob = redb_blob {
+        data: std::ptr::null_mut(),
+        len: 0,
+        capacity: 0,
+    };
+
+    pub(crate) fn new(bytes: Vec<u8>) -> redb_blob {
+        let (data, len, capacity) = into_raw_parts(bytes);
+        redb_blob {
+            data,
+            len,
+            capacity,
+        }
+    }
+
+    /// Takes back the buffer of a blob made by `new`; an empty blob yields an empty `Vec`.
+    ///
+    /// # Safety
+    /// The blob must be `EMPTY` or come from `new` with its fields unchanged, and must not be
+    /// used afterwards.
+    pub(crate) unsafe fn into_vec(self) -> Vec<u8> {
+        unsafe { from_raw_parts(self.data, self.len, self.capacity) }
+    }
+}
+
+impl redb_u64_array {
+    pub(crate) const EMPTY: redb_u64_array = redb_u64_array {
+        data: std::ptr::null_mut(),
+        len: 0,
+        capacity: 0,
+    };
+
+    pub(crate) fn new(values: Vec<u64>) -> redb_u64_array {
+        let (data, len, capacity) = into_raw_parts(values);
+        redb_u64_array {
+            data,
+            len,
+            capacity,
+        }
+    }
+
+    /// Takes back the buffer of an array made by `new`, like `redb_blob::into_vec`.
+    ///
+    /// # Safety
+    /// The array must be `EMPTY` or come from `new` with its fields unchanged, and must not be
+    /// used afterwards.
+    pub(crate) unsafe fn into_vec(self) -> Vec<u64> {
+        unsafe { from_raw_parts(self.data, self.len, self.capacity) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn in_memory_database() -> *mut c_void {
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        db
+    }
+
+    fn codec(codec: redb_value_codec) -> redb_table_codec_options {
+        redb_table_codec_options {
+            codec,
+            level: 0,
+            dictionary: ptr::null(),
+            dictionary_len: 0,
+        }
+    }
+
+    fn write_entries(db: *mut c_void, options: &redb_table_codec_options) {
+        let mut tx = ptr::null_mut();
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        let mut table = ptr::null_mut();
+        assert_eq!(
+            redb_write_tx_open_table_with_codec(tx, c"t".as_ptr(), options, &mut table),
+            REDB_OK
+        );
+        for (key, value) in [(&b"a"[..], &b"first"[..]), (b"b", b""), (b"c", &[7; 300])] {
+            assert_eq!(
+                redb_insert(table, key.as_ptr(), key.len(), value.as_ptr(), value.len()),
+                REDB_OK
+            );
+        }
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+    }
+
+    fn read_entries(db: *mut c_void, options: &redb_table_codec_options) {
+        let mut tx = ptr::null_mut();
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        let mut table = ptr::null_mut();
+        assert_eq!(
+            redb_read_tx_open_table_with_codec(tx, c"t".as_ptr(), options, &mut table),
+            REDB_OK
+        );
+
+        let mut value = redb_blob::EMPTY;
+        assert_eq!(redb_get(table, b"a".as_ptr(), 1, &mut value), REDB_OK);
+        assert_eq!(as_slice(&value), b"first");
+        redb_free_blob(&mut value);
+
+        let mut iter = ptr::null_mut();
+        assert_eq!(redb_iter(table, &mut iter), REDB_OK);
+        let mut entries = Vec::new();
+        let (mut key, mut value) = (redb_blob::EMPTY, redb_blob::EMPTY);
+        while redb_iter_next(iter, &mut key, &mut value) == REDB_OK {
+            entries.push((as_slice(&key).to_vec(), as_slice(&value).len()));
+            redb_free_blob(&mut key);
+            redb_free_blob(&mut value);
+        }
+        assert_eq!(
+            entries,
+            [(b"a".to_vec(), 5), (b"b".to_vec(), 0), (b"c".to_vec(), 300)]
+        );
+
+        redb_free_iter(iter);
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+    }
+
+    fn as_slice(blob: &redb_blob) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(blob.data, blob.len) }
+    }
+
+    #[test]
+    fn keeps_the_allocation_capacity() {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(b"value");
+        let blob = redb_blob::new(bytes);
+        assert_eq!((blob.len, blob.capacity), (5, 64));
+
+        let bytes = unsafe { blob.into_vec() };
+        assert_eq!(bytes, b"value");
+        assert_eq!(bytes.capacity(), 64);
+    }
+
+    #[test]
+    fn free_resets_the_blob() {
+        let mut blob = redb_blob::new(b"value".to_vec());
+        redb_free_blob(&mut blob);
+        assert!(blob.data.is_null());
+        assert_eq!((blob.len, blob.capacity), (0, 0));
+
+        // freeing again, or freeing nothing, does nothing
+        redb_free_blob(&mut blob);
+        redb_free_blob(ptr::null_mut());
+    }
+
+    #[test]
+    fn frees_empty_blobs() {
+        let mut blob = redb_blob::new(Vec::new());
+        assert!(!blob.data.is_null());
+        redb_free_blob(&mut blob);
+
+        let mut blob = redb_blob::EMPTY;
+        redb_free_blob(&mut blob);
+    }
+
+    #[test]
+    fn frees_values_and_entries() {
+        let db = in_memory_database();
+        let options = codec(redb_value_codec::None);
+        write_entries(db, &options);
+        read_entries(db, &options);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn frees_decoded_values() {
+        let options = codec(redb_value_codec::Lz4);
+        let db = in_memory_database();
+        write_entries(db, &options);
+        read_entries(db, &options);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn frees_database_images() {
+        let db = in_memory_database();
+        let options = codec(redb_value_codec::None);
+        write_entries(db, &options);
+        let mut image = redb_blob::EMPTY;
+        assert_eq!(redb_database_to_bytes(db, &mut image), REDB_OK);
+        redb_free_database(db);
+
+        let mut copy = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(image.data, image.len, ptr::null(), &mut copy),
+            REDB_OK
+        );
+        redb_free_blob(&mut image);
+        read_entries(copy, &options);
+        redb_free_database(copy);
+    }
+
+    #[test]
+    fn frees_savepoint_lists() {
+        let db = in_memory_database();
+        let mut tx = ptr::null_mut();
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        let mut id = 0;
+        assert_eq!(redb_write_tx_persistent_savepoint(tx, &mut id), REDB_OK);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        let mut ids = redb_u64_array::EMPTY;
+        assert_eq!(
+            redb_write_tx_lists_persistent_savepoint(tx, &mut ids),
+            REDB_OK
+        );
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(ids.data, ids.len) },
+            [id]
+        );
+        redb_free_u64_array(&mut ids);
+        assert!(ids.data.is_null());
+        redb_free_u64_array(&mut ids);
+        redb_free_u64_array(ptr::null_mut());
+
+        redb_free_write_transaction(tx);
+        redb_free_database(db);
+    }
+}
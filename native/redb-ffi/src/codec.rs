@@ -0,0 +1,252 @@
+//! Per-value compression for tables opened with a codec.
+//!
+//! ```text
+//! value := 0:u8 data
+//!        | codec:u8 len:u32 compressed
+//! ```
+//!
+//! A table switches to this format the first time it is opened for writing with a compressing
+//! codec: its existing values are encoded and its name is recorded by `schema`. From then on every
+//! value starts with a one byte codec id, so entries written with different codecs can live side
+//! by side and are always decoded with the codec that wrote them. `len` is the little-endian
+//! decompressed length. Values that do not shrink are stored uncompressed. Tables that never
+//! switched keep their values as they are, whatever codec they are opened with; switched tables
+//! opened without a codec still decode their values and write new ones stored.
+
+use std::{borrow::Cow, sync::Mutex};
+
+const STORED: u8 = 0;
+const LZ4: u8 = 1;
+const ZSTD: u8 = 2;
+
+const COMPRESSED_HEADER_LEN: usize = 1 + 4;
+
+// the most a byte of compressed data expands to, which bounds the length a header may claim
+// before anything is allocated for it
+const LZ4_MAX_RATIO: usize = 255;
+// a three byte block header and one byte expand to a 128 KiB run
+const ZSTD_MAX_RATIO: usize = 128 * 1024 / 4;
+
+#[derive(Debug)]
+pub(crate) struct CodecError;
+
+pub(crate) enum Codec {
+    None,
+    Lz4,
+    Zstd { level: i32, dictionary: Vec<u8> },
+}
+
+pub(crate) struct ValueCodec {
+    codec: Codec,
+    zstd_compressor: Option<Mutex<zstd::bulk::Compressor<'static>>>,
+    zstd_decompressor: Mutex<zstd::bulk::Decompressor<'static>>,
+}
+
+impl ValueCodec {
+    pub(crate) fn new(codec: Codec) -> Result<Self, CodecError> {
+        let dictionary = match &codec {
+            Codec::Zstd { dictionary, .. } => dictionary.as_slice(),
+            _ => &[],
+        };
+
+        let zstd_compressor = match &codec {
+            Codec::Zstd { level, dictionary } => Some(Mutex::new(
+                zstd::bulk::Compressor::with_dictionary(*level, dictionary)
+                    .map_err(|_| CodecError)?,
+            )),
+            _ => None,
+        };
+        // values written with zstd stay readable after switching the table to another codec
+        let zstd_decompressor = Mutex::new(
+            zstd::bulk::Decompressor::with_dictionary(dictionary).map_err(|_| CodecError)?,
+        );
+
+        Ok(Self {
+            codec,
+            zstd_compressor,
+            zstd_decompressor,
+        })
+    }
+
+    /// Whether writing through this codec switches a table to the codec format.
+    pub(crate) fn compresses(&self) -> bool {
+        !matches!(self.codec, Codec::None)
+    }
+
+    pub(crate) fn encode(&self, value: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let (id, compressed) = match &self.codec {
+            Codec::None => return Ok(stored(value)),
+            Codec::Lz4 => (LZ4, lz4_flex::block::compress(value)),
+            Codec::Zstd { .. } => {
+                let mut compressor = self.zstd_compressor.as_ref().unwrap().lock().unwrap();
+                (ZSTD, compressor.compress(value).map_err(|_| CodecError)?)
+            }
+        };
+
+        // the stored form costs a single header byte
+        if compressed.len() + COMPRESSED_HEADER_LEN > value.len() {
+            return Ok(stored(value));
+        }
+
+        let len = u32::try_from(value.len()).map_err(|_| CodecError)?;
+        let mut encoded = Vec::with_capacity(COMPRESSED_HEADER_LEN + compressed.len());
+        encoded.push(id);
+        encoded.extend_from_slice(&len.to_le_bytes());
+        encoded.extend_from_slice(&compressed);
+        Ok(encoded)
+    }
+
+    pub(crate) fn decode<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, CodecError> {
+        let (&id, rest) = data.split_first().ok_or(CodecError)?;
+        if id == STORED {
+            return Ok(Cow::Borrowed(rest));
+        }
+
+        let (len, compressed) = rest.split_first_chunk::<4>().ok_or(CodecError)?;
+        let len = u32::from_le_bytes(*len) as usize;
+        let max_ratio = match id {
+            LZ4 => LZ4_MAX_RATIO,
+            ZSTD => ZSTD_MAX_RATIO,
+            _ => return Err(CodecError),
+        };
+        if len > compressed.len().saturating_mul(max_ratio) {
+            return Err(CodecError);
+        }
+
+        let decompressed = match id {
+            LZ4 => {
+                let mut out = vec![0; len];
+                match lz4_flex::block::decompress_into(compressed, &mut out) {
+                    Ok(n) if n == len => out,
+                    _ => return Err(CodecError),
+                }
+            }
+            ZSTD => {
+                let mut decompressor = self.zstd_decompressor.lock().unwrap();
+                decompressor
+                    .decompress(compressed, len)
+                    .map_err(|_| CodecError)?
+            }
+            _ => return Err(CodecError),
+        };
+
+        if decompressed.len() != len {
+            return Err(CodecError);
+        }
+        Ok(Cow::Owned(decompressed))
+    }
+}
+
+fn stored(value: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(1 + value.len());
+    encoded.push(STORED);
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    const VALUE: &[u8] = &[b'a'; 256];
+
+    fn options(codec: redb_value_codec) -> redb_table_codec_options {
+        redb_table_codec_options {
+            codec,
+            level: 0,
+            dictionary: ptr::null(),
+            dictionary_len: 0,
+        }
+    }
+
+    fn write(db: *mut c_void, codec: Option<redb_value_codec>, key: &[u8], value: &[u8]) {
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        let code = match codec {
+            Some(codec) => {
+                redb_write_tx_open_table_with_codec(tx, c"t".as_ptr(), &options(codec), &mut table)
+            }
+            None => redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+        };
+        assert_eq!(code, REDB_OK);
+        assert_eq!(
+            redb_insert(table, key.as_ptr(), key.len(), value.as_ptr(), value.len()),
+            REDB_OK
+        );
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+    }
+
+    fn read(db: *mut c_void, codec: Option<redb_value_codec>, key: &[u8]) -> Vec<u8> {
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        let code = match codec {
+            Some(codec) => {
+                redb_read_tx_open_table_with_codec(tx, c"t".as_ptr(), &options(codec), &mut table)
+            }
+            None => redb_read_tx_open_table(tx, c"t".as_ptr(), &mut table),
+        };
+        assert_eq!(code, REDB_OK);
+        let mut value = redb_blob::EMPTY;
+        assert_eq!(
+            redb_get(table, key.as_ptr(), key.len(), &mut value),
+            REDB_OK
+        );
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+        unsafe { value.into_vec() }
+    }
+
+    #[test]
+    fn reads_values_written_before_the_codec() {
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+
+        write(db, None, b"legacy", VALUE);
+        assert_eq!(read(db, Some(redb_value_codec::Lz4), b"legacy"), VALUE);
+
+        // writing without compression leaves the stored bytes alone
+        write(db, Some(redb_value_codec::None), b"plain", VALUE);
+        assert_eq!(read(db, None, b"plain"), VALUE);
+        assert_eq!(read(db, None, b"legacy"), VALUE);
+
+        // the first compressed write switches the table over, legacy values included
+        write(db, Some(redb_value_codec::Lz4), b"compressed", VALUE);
+        for key in [&b"legacy"[..], b"plain", b"compressed"] {
+            assert_eq!(read(db, Some(redb_value_codec::Zstd), key), VALUE);
+        }
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn plain_opens_keep_the_codec_format() {
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+
+        write(db, Some(redb_value_codec::Lz4), b"compressed", VALUE);
+        write(db, None, b"plain", VALUE);
+        for key in [&b"compressed"[..], b"plain"] {
+            assert_eq!(read(db, None, key), VALUE);
+            assert_eq!(read(db, Some(redb_value_codec::Lz4), key), VALUE);
+        }
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn rejects_lengths_the_data_cannot_hold() {
+        let codec = super::ValueCodec::new(super::Codec::None).unwrap();
+        for id in [super::LZ4, super::ZSTD] {
+            let mut data = vec![id];
+            data.extend_from_slice(&u32::MAX.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            assert!(codec.decode(&data).is_err());
+        }
+    }
+}
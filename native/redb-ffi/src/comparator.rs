@@ -0,0 +1,173 @@
+//! Key orderings supplied by the host, registered under the type name stored with the table.
+
+use crate::redb_key_comparator;
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+static REGISTRY: Mutex<BTreeMap<String, Arc<HostComparator>>> = Mutex::new(BTreeMap::new());
+
+pub(crate) struct HostComparator {
+    vtable: redb_key_comparator,
+}
+
+// the host is responsible for making its comparator safe to call from any thread
+unsafe impl Send for HostComparator {}
+unsafe impl Sync for HostComparator {}
+
+impl HostComparator {
+    pub(crate) fn compare(&self, data1: &[u8], data2: &[u8]) -> Ordering {
+        let order = (self.vtable.compare)(
+            self.vtable.user_data,
+            data1.as_ptr(),
+            data1.len(),
+            data2.as_ptr(),
+            data2.len(),
+        );
+        order.cmp(&0)
+    }
+}
+
+impl fmt::Debug for HostComparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostComparator")
+            .field("user_data", &self.vtable.user_data)
+            .finish()
+    }
+}
+
+// a registration is only ever equal to itself
+impl PartialEq for HostComparator {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Registers `vtable` for `type_name`, replacing any earlier registration. Tables that are
+/// already open keep the comparator they were opened with.
+pub(crate) fn register(type_name: &str, vtable: redb_key_comparator) {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .insert(type_name.to_owned(), Arc::new(HostComparator { vtable }));
+}
+
+pub(crate) fn unregister(type_name: &str) -> bool {
+    REGISTRY.lock().unwrap().remove(type_name).is_some()
+}
+
+pub(crate) fn lookup(type_name: &str) -> Option<Arc<HostComparator>> {
+    REGISTRY.lock().unwrap().get(type_name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    const TYPE_NAME: &std::ffi::CStr = c"test.Reverse";
+
+    extern "C" fn reverse(
+        _: *mut c_void,
+        data1: *const u8,
+        len1: usize,
+        data2: *const u8,
+        len2: usize,
+    ) -> i32 {
+        let (data1, data2) = unsafe {
+            (
+                std::slice::from_raw_parts(data1, len1),
+                std::slice::from_raw_parts(data2, len2),
+            )
+        };
+        data2.cmp(data1) as i32
+    }
+
+    fn descriptors() -> [redb_type_descriptor; 2] {
+        let custom = redb_type_descriptor {
+            kind: redb_type_kind::Custom,
+            elements: ptr::null(),
+            element_count: 0,
+            array_len: 0,
+            name: TYPE_NAME.as_ptr(),
+        };
+        let bytes = redb_type_descriptor {
+            kind: redb_type_kind::Bytes,
+            name: ptr::null(),
+            ..custom
+        };
+        [custom, bytes]
+    }
+
+    fn read_keys(db: *mut c_void) -> Result<Vec<Vec<u8>>, i32> {
+        let [key_type, value_type] = descriptors();
+        let (mut tx, mut table, mut iter) = (ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        let code =
+            redb_read_tx_open_typed_table(tx, c"t".as_ptr(), &key_type, &value_type, &mut table);
+        if code != REDB_OK {
+            redb_free_read_transaction(tx);
+            return Err(code);
+        }
+
+        assert_eq!(redb_iter(table, &mut iter), REDB_OK);
+        let mut keys = Vec::new();
+        loop {
+            let (mut key, mut value) = (redb_blob::EMPTY, redb_blob::EMPTY);
+            if redb_iter_next(iter, &mut key, &mut value) != REDB_OK {
+                break;
+            }
+            keys.push(unsafe { key.into_vec() });
+            redb_free_blob(&mut value);
+        }
+        redb_free_iter(iter);
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+        Ok(keys)
+    }
+
+    #[test]
+    fn orders_keys_with_the_registered_comparator() {
+        let comparator = redb_key_comparator {
+            user_data: ptr::null_mut(),
+            compare: reverse,
+        };
+        assert_eq!(
+            redb_register_key_comparator(TYPE_NAME.as_ptr(), &comparator),
+            REDB_OK
+        );
+
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        let [key_type, value_type] = descriptors();
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_typed_table(tx, c"t".as_ptr(), &key_type, &value_type, &mut table),
+            REDB_OK
+        );
+        for key in [b"b", b"a", b"c"] {
+            assert_eq!(
+                redb_insert(table, key.as_ptr(), 1, b"v".as_ptr(), 1),
+                REDB_OK
+            );
+        }
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+
+        assert_eq!(
+            read_keys(db),
+            Ok(vec![b"c".to_vec(), b"b".to_vec(), b"a".to_vec()])
+        );
+
+        assert_eq!(redb_unregister_key_comparator(TYPE_NAME.as_ptr()), REDB_OK);
+        assert_eq!(read_keys(db), Err(REDB_ERROR_UNSUPPORTED_TYPE));
+        redb_free_database(db);
+    }
+}
@@ -0,0 +1,248 @@
+//! Copies tables between databases without knowing their key and value types.
+
+use crate::raw::{self, RawKey, RawValue};
+use crate::schema;
+use redb::{
+    MultimapTableDefinition, MultimapTableHandle, ReadTransaction, ReadableMultimapTable,
+    ReadableTable, TableDefinition, TableHandle, WriteTransaction,
+};
+
+#[derive(Default)]
+pub(crate) struct CopyStats {
+    pub tables: u64,
+    pub entries: u64,
+}
+
+/// Copies every table and multimap table visible in `src` into `dest`, preserving table kinds
+/// and key/value type names. Tables already present in `dest` must not contain any entries. The
+/// reserved `schema` tables always come along, but are not counted as tables.
+pub(crate) fn copy_tables(
+    src: &ReadTransaction,
+    dest: &WriteTransaction,
+    filter: impl Fn(&str) -> bool,
+) -> Result<CopyStats, redb::Error> {
+    let mut stats = CopyStats::default();
+
+    for handle in src.list_tables()? {
+        if schema::is_reserved(handle.name()) {
+            copy_table(src, dest, handle.name())?;
+            continue;
+        }
+        if !filter(handle.name()) {
+            continue;
+        }
+        stats.entries += copy_table(src, dest, handle.name())?;
+        stats.tables += 1;
+    }
+
+    for handle in src.list_multimap_tables()? {
+        if !filter(handle.name()) {
+            continue;
+        }
+        stats.entries += copy_multimap_table(src, dest, handle.name())?;
+        stats.tables += 1;
+    }
+
+    Ok(stats)
+}
+
+pub(crate) fn copy_table(
+    src: &ReadTransaction,
+    dest: &WriteTransaction,
+    name: &str,
+) -> Result<u64, redb::Error> {
+    let definition = TableDefinition::<RawKey, RawValue>::new(name);
+    let schema = raw::probe_schema(|| src.open_table(definition).map(|_| ()))?;
+
+    raw::with_schema(&schema, || {
+        let src_table = src.open_table(definition)?;
+        let mut dest_table = dest.open_table(definition)?;
+
+        let mut entries = 0;
+        for entry in src_table.iter()? {
+            let (key, value) = entry?;
+            raw::set_pending(key.value(), None);
+            dest_table.insert(key.value(), value.value())?;
+            entries += 1;
+        }
+
+        Ok(entries)
+    })
+}
+
+pub(crate) fn copy_multimap_table(
+    src: &ReadTransaction,
+    dest: &WriteTransaction,
+    name: &str,
+) -> Result<u64, redb::Error> {
+    let definition = MultimapTableDefinition::<RawKey, RawValue>::new(name);
+    let schema = raw::probe_schema(|| src.open_multimap_table(definition).map(|_| ()))?;
+
+    raw::with_schema(&schema, || {
+        let src_table = src.open_multimap_table(definition)?;
+        let mut dest_table = dest.open_multimap_table(definition)?;
+
+        let mut entries = 0;
+        for entry in src_table.iter()? {
+            let (key, values) = entry?;
+            for value in values {
+                let value = value?;
+                raw::set_pending(key.value(), Some(value.value()));
+                dest_table.insert(key.value(), value.value())?;
+                entries += 1;
+            }
+        }
+
+        Ok(entries)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("redb-ffi-{name}-{}.redb", std::process::id()))
+    }
+
+    fn c_path(path: &Path) -> std::ffi::CString {
+        std::ffi::CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    fn insert_entry(db: *mut c_void) {
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        assert_eq!(
+            redb_insert(table, b"k".as_ptr(), 1, b"v".as_ptr(), 1),
+            REDB_OK
+        );
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+    }
+
+    fn read_entry(db: *mut c_void) -> Vec<u8> {
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_read_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        let mut value = redb_blob::EMPTY;
+        assert_eq!(redb_get(table, b"k".as_ptr(), 1, &mut value), REDB_OK);
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+        unsafe { value.into_vec() }
+    }
+
+    #[test]
+    fn vacuum_swap_keeps_the_backend() {
+        let (path, vacuum_path) = (temp_path("swap"), temp_path("swap-vacuum"));
+        let (c_path, c_vacuum_path) = (c_path(&path), c_path(&vacuum_path));
+        let key = [7u8; backends::ENCRYPTION_KEY_SIZE];
+        let options = redb_database_options {
+            cache_size: 1 << 20,
+            backend: redb_backend::EncryptedFile,
+            storage: ptr::null(),
+            encryption_key: key.as_ptr(),
+            read_only: false,
+            lock_timeout_ms: 0,
+        };
+
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database(c_path.as_ptr(), &options, &mut db),
+            REDB_OK
+        );
+        insert_entry(db);
+        assert_eq!(
+            redb_vacuum_into(db, c_vacuum_path.as_ptr(), ptr::null_mut()),
+            REDB_OK
+        );
+        // the copy is written encrypted too
+        assert!(redb::Database::open(&vacuum_path).is_err());
+
+        assert_eq!(
+            redb_vacuum_swap(&mut db, c_path.as_ptr(), c_vacuum_path.as_ptr()),
+            REDB_OK
+        );
+        assert_eq!(read_entry(db), b"v");
+        insert_entry(db);
+
+        // a failed rename reopens the original file and reports why it failed
+        assert_eq!(
+            redb_vacuum_swap(&mut db, c_path.as_ptr(), c_vacuum_path.as_ptr()),
+            REDB_ERROR_NOT_FOUND
+        );
+        assert_eq!(read_entry(db), b"v");
+
+        redb_free_database(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn vacuum_swap_keeps_the_original_when_the_copy_does_not_open() {
+        let (path, vacuum_path) = (temp_path("swap-broken"), temp_path("swap-broken-vacuum"));
+        let (c_path, c_vacuum_path) = (c_path(&path), c_path(&vacuum_path));
+
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database(c_path.as_ptr(), ptr::null(), &mut db),
+            REDB_OK
+        );
+        insert_entry(db);
+        std::fs::write(&vacuum_path, [0xa5; 4096]).unwrap();
+
+        assert_ne!(
+            redb_vacuum_swap(&mut db, c_path.as_ptr(), c_vacuum_path.as_ptr()),
+            REDB_OK
+        );
+        assert!(!db.is_null());
+        assert_eq!(read_entry(db), b"v");
+        insert_entry(db);
+        assert_eq!(std::fs::read(&vacuum_path).unwrap(), [0xa5; 4096]);
+
+        redb_free_database(db);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&vacuum_path).unwrap();
+    }
+
+    #[test]
+    fn save_as_replaces_existing_files_only_when_it_succeeds() {
+        let path = temp_path("save-as");
+        let c_path = c_path(&path);
+        std::fs::write(&path, b"old").unwrap();
+
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        insert_entry(db);
+
+        // a directory where the temporary copy goes makes the save fail before the rename
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".{}.tmp", std::process::id()));
+        std::fs::create_dir(&temp_path).unwrap();
+        assert_ne!(redb_save_as(db, c_path.as_ptr(), ptr::null_mut()), REDB_OK);
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        std::fs::remove_dir(&temp_path).unwrap();
+
+        let mut stats = redb_copy_stats {
+            tables: 0,
+            entries: 0,
+        };
+        assert_eq!(redb_save_as(db, c_path.as_ptr(), &mut stats), REDB_OK);
+        assert_eq!((stats.tables, stats.entries), (1, 1));
+        redb_free_database(db);
+
+        assert_eq!(redb_open_database(c_path.as_ptr(), &mut db), REDB_OK);
+        assert_eq!(read_entry(db), b"v");
+        redb_free_database(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
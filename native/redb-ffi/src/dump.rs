@@ -0,0 +1,474 @@
+//! Logical dump format, independent of redb's page layout.
+//!
+//! ```text
+//! dump    := "REDBDUMP" version:u32 table* 0:u8
+//! table   := kind:u8 name:str key:type value:type count:u64 records entry*
+//! type    := classification:u8 name:str has_width:u8 width:u64
+//! records := 0:u8 codec:u8
+//!          | 1:u8 key_type:str value_type:str encoding:str version:u32 codec:u8
+//! entry   := key:bytes value:bytes
+//! str     := bytes (UTF-8)
+//! bytes   := len:u32 data
+//! ```
+//!
+//! All integers are little-endian. `classification` is the `type_names` constant the type name is
+//! rebuilt with. `records` carries what the reserved tables record about the table, which are not
+//! dumped themselves; version 1 dumps have no `records`. Entries are stored in the order of the source table, which lets them be appended
+//! to the destination without knowing how their keys sort; the order is still checked on import
+//! wherever this build knows the ordering.
+
+use crate::raw::{self, RawKey, RawSchema, RawValue};
+use crate::schema::{self, TableSchema};
+use crate::type_names;
+use redb::{
+    MultimapTableDefinition, MultimapTableHandle, ReadTransaction, ReadableMultimapTable,
+    ReadableTable, ReadableTableMetadata, TableDefinition, TableHandle, TypeName, WriteTransaction,
+};
+use std::io::{self, BufRead, Read, Write};
+
+const MAGIC: &[u8; 8] = b"REDBDUMP";
+const VERSION: u32 = 2;
+
+const KIND_END: u8 = 0;
+const KIND_TABLE: u8 = 1;
+const KIND_MULTIMAP_TABLE: u8 = 2;
+
+#[derive(Debug)]
+pub(crate) enum DumpError {
+    Io(io::Error),
+    /// The stream is not a dump, or was written by a newer version
+    Format,
+    /// The entries of a table are not valid encodings of its types, or not in the order the table
+    /// sorts them
+    Corrupted,
+    /// A key or value type name cannot be recreated by this build
+    UnsupportedType,
+    Redb(redb::Error),
+}
+
+impl From<io::Error> for DumpError {
+    fn from(err: io::Error) -> Self {
+        DumpError::Io(err)
+    }
+}
+
+macro_rules! impl_from_redb_error {
+    ($($t:ty),+) => {
+        $(
+            impl From<$t> for DumpError {
+                fn from(err: $t) -> Self {
+                    DumpError::Redb(err.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_from_redb_error!(redb::Error, redb::StorageError, redb::TableError);
+
+#[derive(Default)]
+pub(crate) struct DumpStats {
+    pub tables: u64,
+    pub entries: u64,
+}
+
+fn write_bytes(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(data)
+}
+
+fn write_type(
+    writer: &mut impl Write,
+    type_name: &TypeName,
+    width: Option<usize>,
+) -> Result<(), DumpError> {
+    let classification = type_names::classification(type_name).ok_or(DumpError::UnsupportedType)?;
+    writer.write_all(&[classification])?;
+    write_bytes(writer, type_name.name().as_bytes())?;
+    writer.write_all(&[u8::from(width.is_some())])?;
+    writer.write_all(&(width.unwrap_or(0) as u64).to_le_bytes())?;
+    Ok(())
+}
+
+fn write_header(
+    writer: &mut impl Write,
+    kind: u8,
+    name: &str,
+    schema: &RawSchema,
+    count: u64,
+) -> Result<(), DumpError> {
+    writer.write_all(&[kind])?;
+    write_bytes(writer, name.as_bytes())?;
+    write_type(writer, &schema.key_type, schema.key_width)?;
+    write_type(writer, &schema.value_type, schema.value_width)?;
+    writer.write_all(&count.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_records(
+    writer: &mut impl Write,
+    tx: &ReadTransaction,
+    name: &str,
+) -> Result<(), DumpError> {
+    match schema::get(tx, name)? {
+        Some(record) => {
+            writer.write_all(&[1])?;
+            write_bytes(writer, record.key_type.as_bytes())?;
+            write_bytes(writer, record.value_type.as_bytes())?;
+            write_bytes(writer, record.encoding.as_bytes())?;
+            writer.write_all(&record.version.to_le_bytes())?;
+        }
+        None => writer.write_all(&[0])?,
+    }
+    writer.write_all(&[u8::from(schema::has_codec(tx, name)?)])?;
+    Ok(())
+}
+
+/// Writes every table in `tx` accepted by `filter` to `writer`. The reserved tables are left out.
+pub(crate) fn export(
+    tx: &ReadTransaction,
+    writer: &mut impl Write,
+    filter: impl Fn(&str) -> bool,
+) -> Result<DumpStats, DumpError> {
+    let mut stats = DumpStats::default();
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+
+    for handle in tx.list_tables()? {
+        if schema::is_reserved(handle.name()) || !filter(handle.name()) {
+            continue;
+        }
+
+        let definition = TableDefinition::<RawKey, RawValue>::new(handle.name());
+        let schema = raw::probe_schema(|| tx.open_table(definition).map(|_| ()))?;
+        raw::with_schema(&schema, || -> Result<(), DumpError> {
+            let table = tx.open_table(definition)?;
+            write_header(writer, KIND_TABLE, handle.name(), &schema, table.len()?)?;
+            write_records(writer, tx, handle.name())?;
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                write_bytes(writer, key.value())?;
+                write_bytes(writer, value.value())?;
+                stats.entries += 1;
+            }
+            Ok(())
+        })?;
+        stats.tables += 1;
+    }
+
+    for handle in tx.list_multimap_tables()? {
+        if schema::is_reserved(handle.name()) || !filter(handle.name()) {
+            continue;
+        }
+
+        let definition = MultimapTableDefinition::<RawKey, RawValue>::new(handle.name());
+        let schema = raw::probe_schema(|| tx.open_multimap_table(definition).map(|_| ()))?;
+        raw::with_schema(&schema, || -> Result<(), DumpError> {
+            let table = tx.open_multimap_table(definition)?;
+            write_header(
+                writer,
+                KIND_MULTIMAP_TABLE,
+                handle.name(),
+                &schema,
+                table.len()?,
+            )?;
+            write_records(writer, tx, handle.name())?;
+            for entry in table.iter()? {
+                let (key, values) = entry?;
+                for value in values {
+                    write_bytes(writer, key.value())?;
+                    write_bytes(writer, value?.value())?;
+                    stats.entries += 1;
+                }
+            }
+            Ok(())
+        })?;
+        stats.tables += 1;
+    }
+
+    writer.write_all(&[KIND_END])?;
+    writer.flush()?;
+
+    Ok(stats)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(reader: &mut impl Read, buf: &mut Vec<u8>) -> io::Result<()> {
+    let len = read_u32(reader)? as usize;
+    buf.clear();
+    buf.resize(len, 0);
+    reader.read_exact(buf)
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, DumpError> {
+    let mut buf = Vec::new();
+    read_bytes(reader, &mut buf)?;
+    String::from_utf8(buf).map_err(|_| DumpError::Format)
+}
+
+fn read_type(reader: &mut impl Read) -> Result<(TypeName, Option<usize>), DumpError> {
+    let classification = read_u8(reader)?;
+    let name = read_string(reader)?;
+    let has_width = read_u8(reader)? != 0;
+    let width = read_u64(reader)?;
+
+    let type_name = type_names::resolve(classification, &name).ok_or(DumpError::UnsupportedType)?;
+    let width = has_width
+        .then(|| usize::try_from(width).map_err(|_| DumpError::Format))
+        .transpose()?;
+
+    Ok((type_name, width))
+}
+
+struct Records {
+    schema: Option<TableSchema>,
+    codec: bool,
+}
+
+fn read_records(reader: &mut impl Read) -> Result<Records, DumpError> {
+    let schema = match read_u8(reader)? {
+        0 => None,
+        1 => Some(TableSchema {
+            key_type: read_string(reader)?,
+            value_type: read_string(reader)?,
+            encoding: read_string(reader)?,
+            version: read_u32(reader)?,
+        }),
+        _ => return Err(DumpError::Format),
+    };
+    let codec = read_u8(reader)? != 0;
+    Ok(Records { schema, codec })
+}
+
+fn skip_entries(reader: &mut impl Read, count: u64) -> io::Result<()> {
+    for _ in 0..count * 2 {
+        let len = read_u32(reader)?;
+        io::copy(&mut reader.take(u64::from(len)), &mut io::sink())?;
+    }
+    Ok(())
+}
+
+/// Recreates the tables stored in `reader` that are accepted by `filter`, replacing any existing
+/// table with the same name along with its records. Reserved tables in the dump are skipped.
+pub(crate) fn import(
+    tx: &WriteTransaction,
+    reader: &mut impl BufRead,
+    filter: impl Fn(&str) -> bool,
+) -> Result<DumpStats, DumpError> {
+    let mut stats = DumpStats::default();
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    let version = read_u32(reader)?;
+    if &magic != MAGIC || version > VERSION {
+        return Err(DumpError::Format);
+    }
+
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        let kind = read_u8(reader)?;
+        if kind == KIND_END {
+            break;
+        }
+
+        let name = read_string(reader)?;
+        let (key_type, key_width) = read_type(reader)?;
+        let (value_type, value_width) = read_type(reader)?;
+        let count = read_u64(reader)?;
+        let records = if version >= 2 {
+            read_records(reader)?
+        } else {
+            Records {
+                schema: None,
+                codec: false,
+            }
+        };
+
+        if schema::is_reserved(&name) || !filter(&name) {
+            skip_entries(reader, count)?;
+            continue;
+        }
+
+        let schema = RawSchema {
+            key_type,
+            key_width,
+            value_type,
+            value_width,
+        };
+
+        raw::with_schema(&schema, || -> Result<(), DumpError> {
+            match kind {
+                KIND_TABLE => {
+                    let definition = TableDefinition::<RawKey, RawValue>::new(&name);
+                    tx.delete_table(definition)?;
+                    let mut table = tx.open_table(definition)?;
+                    let mut order = raw::EntryOrder::new(&schema, false);
+                    for _ in 0..count {
+                        read_bytes(reader, &mut key)?;
+                        read_bytes(reader, &mut value)?;
+                        if !order.follows(&key, &value) {
+                            return Err(DumpError::Corrupted);
+                        }
+                        raw::set_pending(&key, None);
+                        table.insert(key.as_slice(), value.as_slice())?;
+                    }
+                }
+                KIND_MULTIMAP_TABLE => {
+                    let definition = MultimapTableDefinition::<RawKey, RawValue>::new(&name);
+                    tx.delete_multimap_table(definition)?;
+                    let mut table = tx.open_multimap_table(definition)?;
+                    let mut order = raw::EntryOrder::new(&schema, true);
+                    for _ in 0..count {
+                        read_bytes(reader, &mut key)?;
+                        read_bytes(reader, &mut value)?;
+                        if !order.follows(&key, &value) {
+                            return Err(DumpError::Corrupted);
+                        }
+                        raw::set_pending(&key, Some(&value));
+                        table.insert(key.as_slice(), value.as_slice())?;
+                    }
+                }
+                _ => return Err(DumpError::Format),
+            }
+            Ok(())
+        })?;
+
+        schema::remove(tx, &name)?;
+        if let Some(record) = &records.schema {
+            schema::set(tx, &name, record)?;
+        }
+        if records.codec {
+            schema::set_codec(tx, &name)?;
+        }
+
+        stats.tables += 1;
+        stats.entries += count;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redb::{Database, ReadableDatabase, backends::InMemoryBackend};
+
+    const NUMBERS: TableDefinition<u64, &str> = TableDefinition::new("numbers");
+
+    fn database() -> Database {
+        Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap()
+    }
+
+    fn dump_of(keys: &[u64]) -> Vec<u8> {
+        let schema = RawSchema {
+            key_type: <u64 as redb::Value>::type_name(),
+            key_width: Some(8),
+            value_type: <&str as redb::Value>::type_name(),
+            value_width: None,
+        };
+        let mut dump = Vec::new();
+        dump.extend_from_slice(MAGIC);
+        dump.extend_from_slice(&VERSION.to_le_bytes());
+        write_header(&mut dump, KIND_TABLE, "numbers", &schema, keys.len() as u64).unwrap();
+        // no schema record, no codec
+        dump.extend_from_slice(&[0, 0]);
+        for key in keys {
+            write_bytes(&mut dump, &key.to_le_bytes()).unwrap();
+            write_bytes(&mut dump, b"v").unwrap();
+        }
+        dump.push(KIND_END);
+        dump
+    }
+
+    fn import_into(db: &Database, dump: &[u8]) -> Result<DumpStats, DumpError> {
+        let tx = db.begin_write().unwrap();
+        let stats = import(&tx, &mut &dump[..], |_| true)?;
+        tx.commit().unwrap();
+        Ok(stats)
+    }
+
+    #[test]
+    fn round_trips_tables() {
+        let source = database();
+        let tx = source.begin_write().unwrap();
+        {
+            let mut table = tx.open_table(NUMBERS).unwrap();
+            for key in [3, 1, 2] {
+                table.insert(key, "v").unwrap();
+            }
+        }
+        tx.commit().unwrap();
+
+        let mut dump = Vec::new();
+        export(&source.begin_read().unwrap(), &mut dump, |_| true).unwrap();
+        assert_eq!(dump, dump_of(&[1, 2, 3]));
+
+        let destination = database();
+        let stats = import_into(&destination, &dump).unwrap();
+        assert_eq!((stats.tables, stats.entries), (1, 3));
+        let tx = destination.begin_read().unwrap();
+        let table = tx.open_table(NUMBERS).unwrap();
+        let keys: Vec<u64> = table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect();
+        assert_eq!(keys, [1, 2, 3]);
+    }
+
+    #[test]
+    fn carries_table_records_instead_of_reserved_tables() {
+        let source = database();
+        let tx = source.begin_write().unwrap();
+        tx.open_table(NUMBERS).unwrap().insert(1, "v").unwrap();
+        let record = TableSchema {
+            key_type: "u64".to_owned(),
+            value_type: "&str".to_owned(),
+            encoding: "utf8".to_owned(),
+            version: 3,
+        };
+        schema::set(&tx, "numbers", &record).unwrap();
+        schema::set_codec(&tx, "numbers").unwrap();
+        tx.commit().unwrap();
+
+        let mut dump = Vec::new();
+        let stats = export(&source.begin_read().unwrap(), &mut dump, |_| true).unwrap();
+        assert_eq!(stats.tables, 1);
+
+        let destination = database();
+        import_into(&destination, &dump).unwrap();
+        let tx = destination.begin_read().unwrap();
+        assert_eq!(schema::get(&tx, "numbers").unwrap(), Some(record));
+        assert!(schema::has_codec(&tx, "numbers").unwrap());
+    }
+
+    #[test]
+    fn rejects_entries_out_of_order() {
+        for keys in [&[2, 1][..], &[1, 1]] {
+            let result = import_into(&database(), &dump_of(keys));
+            assert!(matches!(result, Err(DumpError::Corrupted)));
+        }
+    }
+}
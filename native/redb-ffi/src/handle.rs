@@ -0,0 +1,337 @@
+//! The registry behind every handle given to the host.
+//!
+//! A handle is a slot index and a generation packed into a pointer-sized token, so a handle that
+//! was freed, never issued, or refers to a different kind of object is rejected instead of
+//! dereferenced. Lookups return a counted reference rather than a borrow, so an object used from
+//! several threads at once, or freed on one thread while another is still using it, lives until
+//! the last call using it returns.
+
+use std::{
+    any::Any,
+    ffi::c_void,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+// the low half of a token is the slot index plus one, so no token is null; the high half is the
+// slot's generation, which wraps after 2^16 reuses of a slot on 32-bit targets
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: usize = usize::MAX >> INDEX_BITS;
+
+type Object = Arc<dyn Any + Send + Sync>;
+
+struct Slot {
+    generation: usize,
+    value: Option<Object>,
+}
+
+struct Registry {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+static REGISTRY: RwLock<Registry> = RwLock::new(Registry {
+    slots: Vec::new(),
+    free: Vec::new(),
+});
+
+/// Why `take` or `update` could not get exclusive access to an object.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Exclusive {
+    /// The handle is not a live handle to the requested type.
+    Invalid,
+    /// Something other than the handle still holds a reference to the object.
+    Shared,
+}
+
+fn token(index: usize, generation: usize) -> *mut c_void {
+    std::ptr::without_provenance_mut((generation << INDEX_BITS) | (index + 1))
+}
+
+fn slot_of(handle: *mut c_void) -> Option<(usize, usize)> {
+    let token = handle.addr();
+    let index = (token & INDEX_MASK).checked_sub(1)?;
+    Some((index, token >> INDEX_BITS))
+}
+
+impl Registry {
+    /// The live object behind `handle`, if it is a `T`.
+    fn object<T: 'static>(&self, handle: *mut c_void) -> Option<(usize, &Object)> {
+        let (index, generation) = slot_of(handle)?;
+        let slot = self
+            .slots
+            .get(index)
+            .filter(|slot| slot.generation == generation)?;
+        let value = slot.value.as_ref()?;
+        (**value).is::<T>().then_some((index, value))
+    }
+
+    fn release(&mut self, index: usize) -> Option<Object> {
+        let slot = &mut self.slots[index];
+        slot.generation = (slot.generation + 1) & GENERATION_MASK;
+        self.free.push(index);
+        slot.value.take()
+    }
+}
+
+fn downcast<T: Send + Sync + 'static>(value: Object) -> Arc<T> {
+    value
+        .downcast()
+        .unwrap_or_else(|_| unreachable!("the type was checked"))
+}
+
+/// Hands ownership of `value` to the registry and returns its handle.
+pub(crate) fn insert<T: Send + Sync + 'static>(value: T) -> *mut c_void {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    let value = Some(Arc::new(value) as Object);
+
+    if let Some(index) = registry.free.pop() {
+        let slot = &mut registry.slots[index];
+        slot.value = value;
+        return token(index, slot.generation);
+    }
+
+    let index = registry.slots.len();
+    assert!(index < INDEX_MASK, "too many open handles");
+    registry.slots.push(Slot {
+        generation: 0,
+        value,
+    });
+    token(index, 0)
+}
+
+/// Looks up the live `T` behind `handle`.
+pub(crate) fn get<T: Send + Sync + 'static>(handle: *mut c_void) -> Option<Arc<T>> {
+    let registry = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+    let (_, value) = registry.object::<T>(handle)?;
+    Some(downcast(value.clone()))
+}
+
+/// Invalidates `handle` and returns the registry's reference to its `T`, which is dropped once
+/// every call still using it has returned.
+pub(crate) fn remove<T: Send + Sync + 'static>(handle: *mut c_void) -> Option<Arc<T>> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    let (index, _) = registry.object::<T>(handle)?;
+    registry.release(index).map(downcast)
+}
+
+/// Invalidates every handle to a `T` matching `filter`, returning the registry's references.
+pub(crate) fn remove_matching<T: Send + Sync + 'static>(
+    filter: impl Fn(&T) -> bool,
+) -> Vec<Arc<T>> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    let matching: Vec<_> = (0..registry.slots.len())
+        .filter(|&index| {
+            registry.slots[index]
+                .value
+                .as_ref()
+                .and_then(|value| value.downcast_ref::<T>())
+                .is_some_and(&filter)
+        })
+        .collect();
+
+    matching
+        .into_iter()
+        .filter_map(|index| registry.release(index))
+        .map(downcast)
+        .collect()
+}
+
+/// Takes the `T` behind `handle` out of the registry, invalidating the handle, if nothing else
+/// holds a reference to it. The handle stays valid otherwise.
+pub(crate) fn take<T: Send + Sync + 'static>(handle: *mut c_void) -> Result<T, Exclusive> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    let (index, value) = registry.object::<T>(handle).ok_or(Exclusive::Invalid)?;
+    // new references are only handed out under the lock, so the count cannot grow meanwhile
+    if Arc::strong_count(value) > 1 {
+        return Err(Exclusive::Shared);
+    }
+
+    let value = registry.release(index).map(downcast::<T>);
+    Ok(value
+        .and_then(Arc::into_inner)
+        .expect("the registry held the only reference"))
+}
+
+/// Calls `f` with mutable access to the `T` behind `handle` if nothing else holds a reference to
+/// it. Every handle lookup waits until `f` returns, so `f` must be short and must not use handles.
+pub(crate) fn update<T: Send + Sync + 'static, R>(
+    handle: *mut c_void,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, Exclusive> {
+    let mut registry = REGISTRY.write().unwrap_or_else(PoisonError::into_inner);
+    let (index, _) = registry.object::<T>(handle).ok_or(Exclusive::Invalid)?;
+    let value = registry.slots[index]
+        .value
+        .as_mut()
+        .and_then(Arc::get_mut)
+        .ok_or(Exclusive::Shared)?;
+    Ok(f(value.downcast_mut().expect("the type was checked")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Barrier,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn rejects_stale_and_foreign_handles() {
+        let handle = insert(7u32);
+        assert_eq!(get::<u32>(handle).as_deref(), Some(&7));
+        assert!(get::<u64>(handle).is_none());
+        assert!(remove::<u64>(handle).is_none());
+        assert!(get::<u32>(std::ptr::null_mut()).is_none());
+        assert!(get::<u32>(std::ptr::without_provenance_mut(usize::MAX)).is_none());
+
+        assert!(remove::<u32>(handle).is_some());
+        assert!(get::<u32>(handle).is_none());
+        assert!(remove::<u32>(handle).is_none());
+
+        // the slot is reused under a new generation, which the old handle does not match
+        let reused = insert(8u32);
+        assert_ne!(reused, handle);
+        assert!(get::<u32>(handle).is_none());
+        assert_eq!(take::<u32>(reused), Ok(8));
+    }
+
+    #[test]
+    fn keeps_objects_alive_while_in_use() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let handle = insert(Counted(drops.clone()));
+
+        let in_use = get::<Counted>(handle).unwrap();
+        assert!(matches!(take::<Counted>(handle), Err(Exclusive::Shared)));
+        assert_eq!(update(handle, |_: &mut Counted| ()), Err(Exclusive::Shared));
+
+        drop(remove::<Counted>(handle));
+        assert!(get::<Counted>(handle).is_none());
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        drop(in_use);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn updates_unshared_objects() {
+        let handle = insert(1u32);
+        assert_eq!(update(handle, |value: &mut u32| *value += 1), Ok(()));
+        assert_eq!(take::<u32>(handle), Ok(2));
+        assert_eq!(update(handle, |_: &mut u32| ()), Err(Exclusive::Invalid));
+    }
+
+    #[test]
+    fn removes_matching_objects() {
+        let handles = [insert(1u32), insert(2u32), insert(3u32)];
+        let removed = remove_matching(|value: &u32| value % 2 == 1);
+        assert_eq!(removed.len(), 2);
+        assert!(get::<u32>(handles[0]).is_none());
+        assert_eq!(take::<u32>(handles[1]), Ok(2));
+        assert!(get::<u32>(handles[2]).is_none());
+    }
+
+    #[test]
+    fn frees_concurrently_used_handles_once() {
+        const THREADS: usize = 4;
+        let drops = Arc::new(AtomicUsize::new(0));
+        let handle = insert(Counted(drops.clone())).addr();
+        let barrier = Barrier::new(THREADS);
+
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    let handle = std::ptr::without_provenance_mut(handle);
+                    barrier.wait();
+                    for _ in 0..100 {
+                        if let Some(object) = get::<Counted>(handle) {
+                            assert_eq!(object.0.load(Ordering::SeqCst), 0);
+                        }
+                    }
+                    if thread == 0 {
+                        drop(remove::<Counted>(handle));
+                    }
+                });
+            }
+        });
+
+        assert!(get::<Counted>(std::ptr::without_provenance_mut(handle)).is_none());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn shares_table_handles_between_threads() {
+        use crate::*;
+        use std::ptr;
+
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        assert_eq!(
+            redb_insert(table, b"k".as_ptr(), 1, b"v".as_ptr(), 1),
+            REDB_OK
+        );
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_read_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        // the transaction and database stay alive for the table after their handles are freed
+        redb_free_read_transaction(tx);
+        redb_free_database(db);
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_ERROR_INVALID_HANDLE);
+
+        const THREADS: usize = 4;
+        let table = table.addr();
+        let barrier = Barrier::new(THREADS);
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    let table = std::ptr::without_provenance_mut(table);
+                    barrier.wait();
+                    for _ in 0..100 {
+                        let mut value = redb_blob::EMPTY;
+                        match redb_get(table, b"k".as_ptr(), 1, &mut value) {
+                            REDB_OK => {
+                                assert_eq!(unsafe { value.into_vec() }, b"v");
+                            }
+                            code => assert_eq!(code, REDB_ERROR_INVALID_HANDLE),
+                        }
+                    }
+                    if thread == 0 {
+                        redb_free_readonly_table(table);
+                    }
+                });
+            }
+        });
+
+        let mut value = redb_blob::EMPTY;
+        let table = std::ptr::without_provenance_mut(table);
+        assert_eq!(
+            redb_get(table, b"k".as_ptr(), 1, &mut value),
+            REDB_ERROR_INVALID_HANDLE
+        );
+    }
+}
@@ -1,9 +1,33 @@
+// Exports take raw pointers from the host by design and check them before dereferencing;
+// marking each of them `unsafe` would change nothing for C callers.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+mod backends;
+mod blob;
+mod codec;
+mod comparator;
+mod copy;
+mod dump;
+mod handle;
+mod lock;
+mod path;
+mod raw;
+mod schema;
+mod type_names;
+mod typed;
+mod upgrade;
+
 use redb::{
-    ReadableDatabase, ReadableTable, ReadableTableMetadata, TableDefinition, backends::FileBackend,
+    ReadableDatabase, ReadableTable, ReadableTableMetadata, StorageBackend, TableDefinition,
+    backends::FileBackend,
 };
 use std::{
     ffi::{c_char, c_void},
-    fs::OpenOptions,
+    fs::{File, OpenOptions},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, atomic::Ordering},
+    time::Duration,
 };
 
 pub const REDB_OK: i32 = 0;
@@ -33,15 +57,76 @@ pub const REDB_ERROR_READ_TRANSACTION_STILL_IN_USE: i32 = 41;
 // 51- SavepointError
 pub const REDB_ERROR_INVALID_SAVEPOINT: i32 = 51;
 
+// 61- UpgradeError
+pub const REDB_ERROR_UPGRADE: i32 = 61;
+
 // 100- Custom errors
 pub const REDB_ERROR_KEY_NOT_FOUND: i32 = 100;
+pub const REDB_ERROR_INVALID_DUMP: i32 = 101;
+pub const REDB_ERROR_UNSUPPORTED_TYPE: i32 = 102;
+pub const REDB_ERROR_INVALID_ENCRYPTION_KEY: i32 = 103;
+pub const REDB_ERROR_CODEC: i32 = 104;
+pub const REDB_ERROR_READ_ONLY: i32 = 105;
+// an empty path or one containing NUL; paths that do not decode are `REDB_ERROR_INVALID_ENCODING`
+pub const REDB_ERROR_INVALID_PATH: i32 = 106;
+
+// 107- I/O errors, reported in place of `REDB_ERROR_FILE_ERROR` or `REDB_ERROR_STORAGE_ERROR`
+// when the underlying `std::io::ErrorKind` is known. Lock contention is reported as
+// `REDB_ERROR_DATABASE_ALREADY_OPEN`.
+pub const REDB_ERROR_NOT_FOUND: i32 = 107;
+pub const REDB_ERROR_PERMISSION_DENIED: i32 = 108;
+pub const REDB_ERROR_DISK_FULL: i32 = 109;
+pub const REDB_ERROR_READ_ONLY_FILESYSTEM: i32 = 110;
+
+pub const REDB_ERROR_INVALID_ENCODING: i32 = 111;
+pub const REDB_ERROR_SCHEMA_MISMATCH: i32 = 112;
+pub const REDB_ERROR_INVALID_MIGRATION: i32 = 113;
+// a handle that was already freed, never issued, or is of the wrong kind for the call
+pub const REDB_ERROR_INVALID_HANDLE: i32 = 114;
+// the database schema version is newer than the last migration step the host knows
+pub const REDB_ERROR_SCHEMA_VERSION_TOO_NEW: i32 = 115;
+// stored data failed an integrity check
+pub const REDB_ERROR_CORRUPTED: i32 = 116;
+// table names starting with `$redb.` belong to the native layer's own tables
+pub const REDB_ERROR_RESERVED_TABLE_NAME: i32 = 117;
+// an encryption key was given for a file that is not encrypted
+pub const REDB_ERROR_NOT_ENCRYPTED: i32 = 118;
+
+pub const REDB_FILE_FORMAT_VERSION: u8 = 3;
+
+/// Maps the I/O error kinds the host can act on to their own codes, and everything else to
+/// `fallback`.
+fn io_error_code(err: &std::io::Error, fallback: i32) -> i32 {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => REDB_ERROR_NOT_FOUND,
+        std::io::ErrorKind::PermissionDenied => REDB_ERROR_PERMISSION_DENIED,
+        std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => REDB_ERROR_DISK_FULL,
+        std::io::ErrorKind::ReadOnlyFilesystem => REDB_ERROR_READ_ONLY_FILESYSTEM,
+        std::io::ErrorKind::WouldBlock => REDB_ERROR_DATABASE_ALREADY_OPEN,
+        // the compressed and encrypted backends report blocks that fail their checks this way
+        std::io::ErrorKind::InvalidData => REDB_ERROR_CORRUPTED,
+        _ => fallback,
+    }
+}
+
+fn file_error_code(err: &std::io::Error) -> i32 {
+    io_error_code(err, REDB_ERROR_FILE_ERROR)
+}
+
+fn storage_error_code(err: &redb::StorageError) -> i32 {
+    match err {
+        redb::StorageError::Io(err) => io_error_code(err, REDB_ERROR_STORAGE_ERROR),
+        redb::StorageError::Corrupted(_) => REDB_ERROR_CORRUPTED,
+        _ => REDB_ERROR_STORAGE_ERROR,
+    }
+}
 
-pub const fn database_error_code(err: &redb::DatabaseError) -> i32 {
+pub fn database_error_code(err: &redb::DatabaseError) -> i32 {
     match err {
         redb::DatabaseError::DatabaseAlreadyOpen => REDB_ERROR_DATABASE_ALREADY_OPEN,
         redb::DatabaseError::RepairAborted => REDB_ERROR_REPAIR_ABORTED,
         redb::DatabaseError::UpgradeRequired(_) => REDB_ERROR_UPGRADE_REQUIRED,
-        redb::DatabaseError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
+        redb::DatabaseError::Storage(err) => storage_error_code(err),
         _ => REDB_ERROR_FILE_ERROR,
     }
 }
@@ -55,8 +140,8 @@ fn table_error_code(err: &redb::TableError) -> i32 {
         redb::TableError::TableDoesNotExist(_) => REDB_ERROR_TABLE_DOES_NOT_EXIST,
         redb::TableError::TableExists(_) => REDB_ERROR_TABLE_EXISTS,
         redb::TableError::TableAlreadyOpen(_, _) => REDB_ERROR_TABLE_ALREADY_OPEN,
-        redb::TableError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
-        _ => todo!(),
+        redb::TableError::Storage(err) => storage_error_code(err),
+        _ => REDB_ERROR_STORAGE_ERROR,
     }
 }
 
@@ -65,303 +150,2353 @@ fn transaction_error_code(err: &redb::TransactionError) -> i32 {
         redb::TransactionError::ReadTransactionStillInUse(_) => {
             REDB_ERROR_READ_TRANSACTION_STILL_IN_USE
         }
-        redb::TransactionError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
-        _ => todo!(),
+        redb::TransactionError::Storage(err) => storage_error_code(err),
+        _ => REDB_ERROR_STORAGE_ERROR,
     }
 }
 
 fn savepoint_error_code(err: &redb::SavepointError) -> i32 {
     match err {
         redb::SavepointError::InvalidSavepoint => REDB_ERROR_INVALID_SAVEPOINT,
-        redb::SavepointError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
-        _ => todo!(),
+        redb::SavepointError::Storage(err) => storage_error_code(err),
+        _ => REDB_ERROR_STORAGE_ERROR,
+    }
+}
+
+fn commit_error_code(err: redb::CommitError) -> i32 {
+    match err {
+        redb::CommitError::Storage(err) => storage_error_code(&err),
+        err => error_code(&err.into()),
+    }
+}
+
+fn error_code(err: &redb::Error) -> i32 {
+    match err {
+        redb::Error::DatabaseAlreadyOpen => REDB_ERROR_DATABASE_ALREADY_OPEN,
+        redb::Error::RepairAborted => REDB_ERROR_REPAIR_ABORTED,
+        redb::Error::UpgradeRequired(_) => REDB_ERROR_UPGRADE_REQUIRED,
+        redb::Error::PersistentSavepointExists
+        | redb::Error::EphemeralSavepointExists
+        | redb::Error::TransactionInProgress => REDB_ERROR_COMPACTION,
+        redb::Error::TableTypeMismatch { .. } => REDB_ERROR_TABLE_TYPE_MISMATCH,
+        redb::Error::TableIsMultimap(_) => REDB_ERROR_TABLE_IS_MULTIMAP,
+        redb::Error::TableIsNotMultimap(_) => REDB_ERROR_TABLE_IS_NOT_MULTIMAP,
+        redb::Error::TypeDefinitionChanged { .. } => REDB_ERROR_TYPE_DEFINITION_CHANGED,
+        redb::Error::TableDoesNotExist(_) => REDB_ERROR_TABLE_DOES_NOT_EXIST,
+        redb::Error::TableExists(_) => REDB_ERROR_TABLE_EXISTS,
+        redb::Error::TableAlreadyOpen(_, _) => REDB_ERROR_TABLE_ALREADY_OPEN,
+        redb::Error::ReadTransactionStillInUse(_) => REDB_ERROR_READ_TRANSACTION_STILL_IN_USE,
+        redb::Error::InvalidSavepoint => REDB_ERROR_INVALID_SAVEPOINT,
+        redb::Error::Io(err) => io_error_code(err, REDB_ERROR_STORAGE_ERROR),
+        redb::Error::Corrupted(_) => REDB_ERROR_CORRUPTED,
+        _ => REDB_ERROR_STORAGE_ERROR,
+    }
+}
+
+fn dump_error_code(err: &dump::DumpError) -> i32 {
+    match err {
+        dump::DumpError::Io(err) => file_error_code(err),
+        dump::DumpError::Format => REDB_ERROR_INVALID_DUMP,
+        dump::DumpError::Corrupted => REDB_ERROR_CORRUPTED,
+        dump::DumpError::UnsupportedType => REDB_ERROR_UNSUPPORTED_TYPE,
+        dump::DumpError::Redb(err) => error_code(err),
     }
 }
 
+/// Collects an optional array of table names into a filter; a null array accepts every table.
+/// Returns `REDB_ERROR_INVALID_ENCODING` if a name is not valid UTF-8.
+fn table_name_filter(
+    names: *const *const c_char,
+    count: usize,
+) -> Result<impl Fn(&str) -> bool, i32> {
+    let names = if names.is_null() {
+        None
+    } else {
+        let names = unsafe { std::slice::from_raw_parts(names, count) };
+        let names = names
+            .iter()
+            .map(|name| {
+                let c_str = unsafe {
+                    assert!(!name.is_null());
+                    std::ffi::CStr::from_ptr(*name)
+                };
+                c_str
+                    .to_str()
+                    .map(str::to_owned)
+                    .map_err(|_| REDB_ERROR_INVALID_ENCODING)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Some(names)
+    };
+
+    Ok(move |name: &str| {
+        names
+            .as_ref()
+            .is_none_or(|names| names.iter().any(|n| n == name))
+    })
+}
+
 #[repr(C)]
 pub struct redb_database_options {
     pub cache_size: usize,
     pub backend: redb_backend,
+    // required for `redb_backend::Callback`
+    pub storage: *const redb_storage_backend,
+    // 32 bytes, required for `redb_backend::EncryptedFile`
+    pub encryption_key: *const u8,
+    // the file is never modified and write transactions fail to commit; implied by `redb_backend::Mmap`.
+    // Read-only handles share the file with each other but keep writers out
+    pub read_only: bool,
+    // how long to keep retrying while another handle holds the file lock; 0 fails immediately
+    pub lock_timeout_ms: u32,
 }
 
 #[repr(C)]
 pub enum redb_backend {
     File,
     InMemory,
+    Callback,
+    EncryptedFile,
+    CompressedFile,
+    Mmap,
 }
 
+// Callbacks return REDB_OK on success. `close` may be null.
 #[repr(C)]
-pub enum redb_durability {
+#[derive(Clone, Copy)]
+pub struct redb_storage_backend {
+    pub user_data: *mut c_void,
+    pub len: extern "C" fn(user_data: *mut c_void, out_len: *mut u64) -> i32,
+    pub read: extern "C" fn(user_data: *mut c_void, offset: u64, out: *mut u8, len: usize) -> i32,
+    pub write:
+        extern "C" fn(user_data: *mut c_void, offset: u64, data: *const u8, len: usize) -> i32,
+    pub set_len: extern "C" fn(user_data: *mut c_void, len: u64) -> i32,
+    pub sync_data: extern "C" fn(user_data: *mut c_void) -> i32,
+    pub close: Option<extern "C" fn(user_data: *mut c_void) -> i32>,
+}
+
+#[repr(C)]
+pub struct redb_cache_stats {
+    pub evictions: u64,
+}
+
+#[repr(C)]
+pub struct redb_copy_stats {
+    pub tables: u64,
+    pub entries: u64,
+}
+
+#[repr(C)]
+pub struct redb_table_codec_options {
+    pub codec: redb_value_codec,
+    // zstd only; 0 selects the default level
+    pub level: i32,
+    // zstd only; may be null
+    pub dictionary: *const u8,
+    pub dictionary_len: usize,
+}
+
+#[repr(C)]
+pub enum redb_value_codec {
     None,
-    Immediate,
+    Lz4,
+    Zstd,
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_create_database(
-    path: *const c_char,
-    options: *const redb_database_options,
-    out: *mut *mut c_void,
-) -> i32 {
-    let c_str = unsafe {
-        assert!(!path.is_null());
-        std::ffi::CStr::from_ptr(path)
-    };
-    let str_slice = c_str.to_str().unwrap();
+/// Describes a redb key or value type, so tables written by Rust programs can be opened with the
+/// same types. Keys and values of such tables are passed in redb's binary encoding for the type.
+#[repr(C)]
+pub struct redb_type_descriptor {
+    pub kind: redb_type_kind,
+    // the element type of an `Array` or `Descending`, or the `element_count` element types of a
+    // `Tuple`
+    pub elements: *const redb_type_descriptor,
+    pub element_count: usize,
+    // the length of an `Array`
+    pub array_len: usize,
+    // the type name of a `Custom` type, as returned by its Rust `Value::type_name`
+    pub name: *const c_char,
+}
 
-    let db = if !options.is_null() {
-        let opts = unsafe { &*options };
-        let mut builder = redb::Database::builder();
-        builder.set_cache_size(opts.cache_size);
-        match opts.backend {
-            redb_backend::File => {
-                let Ok(file) = OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .create(true)
-                    .truncate(false)
-                    .open(str_slice)
-                else {
-                    return REDB_ERROR_FILE_ERROR;
-                };
+#[repr(C)]
+pub enum redb_type_kind {
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    // f32 and f64 are only valid in value types
+    F32,
+    F64,
+    Str,
+    Bytes,
+    Array,
+    Tuple,
+    // ordered by the comparator registered for its name when used in a key
+    Custom,
+    // an integer, `Str` or `Bytes` element type encoded as itself but sorting in reverse, so a
+    // forward range yields the largest keys first; its Rust type name is `Descending<u64>` and so on
+    Descending,
+}
 
-                let Ok(backend) = FileBackend::new(file) else {
-                    return REDB_ERROR_FILE_ERROR;
-                };
+// `compare` returns a negative number, zero or a positive number when `data1` sorts before, equal
+// to or after `data2`. It is called from whichever thread uses the table.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct redb_key_comparator {
+    pub user_data: *mut c_void,
+    pub compare: extern "C" fn(
+        user_data: *mut c_void,
+        data1: *const u8,
+        len1: usize,
+        data2: *const u8,
+        len2: usize,
+    ) -> i32,
+}
 
-                match builder.create_with_backend(backend) {
-                    Ok(db) => db,
-                    Err(err) => return database_error_code(&err),
-                }
-            }
-            redb_backend::InMemory => {
-                match builder.create_with_backend(redb::backends::InMemoryBackend::new()) {
-                    Ok(db) => db,
-                    Err(err) => return database_error_code(&err),
-                }
-            }
-        }
-    } else {
-        match redb::Database::create(str_slice) {
-            Ok(db) => db,
-            Err(err) => return database_error_code(&err),
-        }
-    };
+// Key types of tables opened with `redb_write_tx_open_numeric_table`
+#[repr(C)]
+pub enum redb_numeric_key {
+    U64,
+    I64,
+    U32,
+    I32,
+}
 
-    unsafe {
-        *out = Box::into_raw(Box::new(db)) as *mut c_void;
-    };
+// One component of a composite key, in its redb encoding
+#[repr(C)]
+pub struct redb_key_part {
+    pub data: *const u8,
+    pub len: usize,
+}
 
-    REDB_OK
+// A byte buffer allocated by the library, valid for `len` bytes. `capacity` describes the
+// allocation and must be passed back unchanged; free the buffer with `redb_free_blob`.
+#[repr(C)]
+pub struct redb_blob {
+    pub data: *mut u8,
+    pub len: usize,
+    pub capacity: usize,
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_open_database(path: *const c_char, out: *mut *mut c_void) -> i32 {
-    let c_str = unsafe {
-        assert!(!path.is_null());
-        std::ffi::CStr::from_ptr(path)
-    };
-    let str_slice = c_str.to_str().unwrap();
+// An array of integers allocated by the library, valid for `len` elements. `capacity` describes
+// the allocation and must be passed back unchanged; free the array with `redb_free_u64_array`.
+#[repr(C)]
+pub struct redb_u64_array {
+    pub data: *mut u64,
+    pub len: usize,
+    pub capacity: usize,
+}
 
-    let db = match redb::Database::open(str_slice) {
-        Ok(db) => db,
-        Err(err) => return database_error_code(&err),
-    };
+// What a table was written with, as recorded by `redb_write_tx_set_table_schema`. The type names
+// and encoding identifier are free-form; strings written by the `get` exports must be freed with
+// `redb_free_string`.
+#[repr(C)]
+pub struct redb_table_schema {
+    pub key_type: *const c_char,
+    pub value_type: *const c_char,
+    pub encoding: *const c_char,
+    pub version: u32,
+}
 
-    unsafe {
-        *out = Box::into_raw(Box::new(db)) as *mut c_void;
-    };
+// One step of a `redb_migrate` run, bringing the database to schema `version`. `migrate` is given
+// the run's write transaction; it must free every table it opens before returning and must not
+// commit, abort or free the transaction. Returning anything but REDB_OK rolls back the whole run.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct redb_migration {
+    pub version: u32,
+    pub user_data: *mut c_void,
+    pub migrate: extern "C" fn(user_data: *mut c_void, tx: *mut c_void) -> i32,
+}
 
-    REDB_OK
+#[repr(C)]
+pub enum redb_durability {
+    None,
+    Immediate,
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_compact_database(db: *mut c_void) -> i32 {
-    let db = unsafe {
-        assert!(!db.is_null());
-        &mut *(db as *mut redb::Database)
-    };
+fn open_file_backend(path: &Path) -> std::io::Result<FileBackend> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    FileBackend::new(file).map_err(|err| match err {
+        redb::DatabaseError::DatabaseAlreadyOpen => std::io::ErrorKind::WouldBlock.into(),
+        redb::DatabaseError::Storage(redb::StorageError::Io(err)) => err,
+        err => std::io::Error::other(err),
+    })
+}
 
-    match db.compact() {
-        Ok(_) => REDB_OK,
-        Err(_) => REDB_ERROR_COMPACTION,
+fn create_with_backend(
+    builder: &redb::Builder,
+    backend: impl redb::StorageBackend,
+    read_only: bool,
+) -> Result<redb::Database, i32> {
+    if !read_only {
+        return builder
+            .create_with_backend(backend)
+            .map_err(|err| database_error_code(&err));
     }
+
+    let backend = backends::ReadOnlyBackend::new(backend).map_err(|err| file_error_code(&err))?;
+    let sealed = backend.sealed();
+    let db = builder
+        .create_with_backend(backend)
+        .map_err(|err| database_error_code(&err))?;
+    sealed.store(true, Ordering::Release);
+
+    Ok(db)
 }
 
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_database(db: *mut c_void) {
-    if db.is_null() {
-        return;
-    }
-    unsafe {
-        drop(Box::from_raw(db as *mut redb::Database));
+pub extern "C" fn redb_create_database(
+    path: *const c_char,
+    options: *const redb_database_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => create_database(&path, options, out),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_create_database`, with the path given as `path_len` bytes of UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_table(table: *mut c_void) {
-    if table.is_null() {
-        return;
-    }
-    unsafe {
-        drop(Box::from_raw(table as *mut redb::Table<&str, i32>));
+pub extern "C" fn redb_create_database_utf8(
+    path: *const u8,
+    path_len: usize,
+    options: *const redb_database_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    match unsafe { path::from_utf8_raw(path, path_len) } {
+        Ok(path) => create_database(&path, options, out),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_create_database`, with the path given as `path_len` UTF-16 code units.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_readonly_table(table: *mut c_void) {
-    if table.is_null() {
-        return;
+pub extern "C" fn redb_create_database_utf16(
+    path: *const u16,
+    path_len: usize,
+    options: *const redb_database_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => create_database(&path, options, out),
+        Err(code) => code,
     }
-    unsafe {
-        drop(Box::from_raw(table as *mut redb::ReadOnlyTable<&str, i32>));
+}
+
+fn create_database(
+    path: &Path,
+    options: *const redb_database_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    let opts = unsafe { options.as_ref() };
+
+    let (db, file, read_only) = match opts {
+        Some(opts)
+            if matches!(
+                opts.backend,
+                redb_backend::InMemory | redb_backend::Callback
+            ) =>
+        {
+            let mut builder = redb::Database::builder();
+            builder.set_cache_size(opts.cache_size);
+
+            let db = if let redb_backend::Callback = opts.backend {
+                let storage = unsafe {
+                    assert!(!opts.storage.is_null());
+                    *opts.storage
+                };
+                let backend = backends::CallbackBackend::new(storage);
+                create_with_backend(&builder, backend, opts.read_only)
+            } else {
+                let backend = redb::backends::InMemoryBackend::new();
+                create_with_backend(&builder, backend, opts.read_only)
+            };
+            (db, None, opts.read_only)
+        }
+        opts => {
+            let file = FileOptions::new(opts);
+            let read_only = file.read_only;
+            (open_file_database(path, &file), Some(file), read_only)
+        }
+    };
+
+    match db {
+        Ok(db) => unsafe {
+            *out = handle::insert(RedbDatabase::new(db, file, read_only));
+        },
+        Err(code) => return code,
     }
+
+    REDB_OK
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_free_write_transaction(tx: *mut c_void) {
-    if tx.is_null() {
-        return;
+/// Opens the database file at `path` with the backend and options in `file`.
+fn open_file_database(path: &Path, file: &FileOptions) -> Result<redb::Database, i32> {
+    let mut builder = redb::Database::builder();
+    if let Some(cache_size) = file.cache_size {
+        builder.set_cache_size(cache_size);
     }
-    unsafe {
-        drop(Box::from_raw(tx as *mut redb::WriteTransaction));
+
+    if file.mmap {
+        let backend = lock::retry_while_locked(file.lock_timeout, || {
+            backends::MmapBackend::new(OpenOptions::new().read(true).open(path)?)
+        })
+        .map_err(|err| file_error_code(&err))?;
+        return create_with_backend(&builder, backend, true);
     }
-}
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_free_read_transaction(tx: *mut c_void) {
-    if tx.is_null() {
-        return;
+    if file.read_only {
+        let backend = lock::retry_while_locked(file.lock_timeout, || {
+            backends::SharedFileBackend::new(OpenOptions::new().read(true).open(path)?)
+        })
+        .map_err(|err| file_error_code(&err))?;
+        return create_in_format(&builder, backend, &file.format, true);
     }
-    unsafe {
-        drop(Box::from_raw(tx as *mut redb::ReadTransaction));
+
+    let backend = lock::retry_while_locked(file.lock_timeout, || open_file_backend(path))
+        .map_err(|err| file_error_code(&err))?;
+    create_in_format(&builder, backend, &file.format, false)
+}
+
+/// Creates or opens a database on `backend`, wrapped in the backend `format` calls for.
+fn create_in_format(
+    builder: &redb::Builder,
+    backend: impl StorageBackend,
+    format: &FileFormat,
+    read_only: bool,
+) -> Result<redb::Database, i32> {
+    match format {
+        FileFormat::Plain => create_with_backend(builder, backend, read_only),
+        FileFormat::Compressed => {
+            let backend =
+                backends::CompressedBackend::new(backend).map_err(|err| file_error_code(&err))?;
+            create_with_backend(builder, backend, read_only)
+        }
+        FileFormat::Encrypted(key) => {
+            let backend = match backends::EncryptedBackend::new(backend, key) {
+                Ok(backend) => backend,
+                Err(backends::EncryptionError::Io(err)) => return Err(file_error_code(&err)),
+                Err(backends::EncryptionError::NotEncrypted) => {
+                    return Err(REDB_ERROR_NOT_ENCRYPTED);
+                }
+                Err(backends::EncryptionError::WrongKey) => {
+                    return Err(REDB_ERROR_INVALID_ENCRYPTION_KEY);
+                }
+            };
+            create_with_backend(builder, backend, read_only)
+        }
     }
 }
 
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_savepoint(savepoint: *mut c_void) {
-    if savepoint.is_null() {
-        return;
-    }
-    unsafe {
-        drop(Box::from_raw(savepoint as *mut redb::Savepoint));
+pub extern "C" fn redb_open_database(path: *const c_char, out: *mut *mut c_void) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => open_database(&path, out),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_open_database`, with the path given as `path_len` bytes of UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_string(s: *mut c_char) {
-    if s.is_null() {
-        return;
-    }
-    unsafe {
-        drop(std::ffi::CString::from_raw(s));
+pub extern "C" fn redb_open_database_utf8(
+    path: *const u8,
+    path_len: usize,
+    out: *mut *mut c_void,
+) -> i32 {
+    match unsafe { path::from_utf8_raw(path, path_len) } {
+        Ok(path) => open_database(&path, out),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_open_database`, with the path given as `path_len` UTF-16 code units.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free(ptr: *mut c_void) {
-    if ptr.is_null() {
-        return;
+pub extern "C" fn redb_open_database_utf16(
+    path: *const u16,
+    path_len: usize,
+    out: *mut *mut c_void,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => open_database(&path, out),
+        Err(code) => code,
     }
+}
+
+fn open_database(path: &Path, out: *mut *mut c_void) -> i32 {
+    let db = match redb::Database::open(path) {
+        Ok(db) => db,
+        Err(err) => return database_error_code(&err),
+    };
+
     unsafe {
-        drop(Box::from_raw(ptr));
-    }
+        *out = handle::insert(RedbDatabase::new(db, Some(FileOptions::default()), false));
+    };
+
+    REDB_OK
 }
 
+/// Reports whether the database file at `path` is currently locked by an open database, in this
+/// or another process. Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_free_blob(blob: *mut u8) {
-    if blob.is_null() {
-        return;
-    }
-    unsafe {
-        drop(Box::from_raw(blob as *mut u8));
+pub extern "C" fn redb_is_file_locked(path: *const c_char, out_locked: *mut bool) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => is_file_locked(&path, out_locked),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_is_file_locked`, with the path given as `path_len` UTF-16 code units.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_begin_write(db: *mut c_void, out: *mut *mut c_void) -> i32 {
-    let db = unsafe {
-        assert!(!db.is_null());
-        &mut *(db as *mut redb::Database)
-    };
+pub extern "C" fn redb_is_file_locked_utf16(
+    path: *const u16,
+    path_len: usize,
+    out_locked: *mut bool,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => is_file_locked(&path, out_locked),
+        Err(code) => code,
+    }
+}
 
-    match db.begin_write() {
-        Ok(tx) => {
+fn is_file_locked(path: &Path, out_locked: *mut bool) -> i32 {
+    match lock::is_locked(path) {
+        Ok(locked) => {
             unsafe {
-                *out = Box::into_raw(Box::new(tx)) as *mut c_void;
-            };
+                *out_locked = locked;
+            }
             REDB_OK
         }
-        Err(err) => transaction_error_code(&err),
+        Err(err) => file_error_code(&err),
     }
 }
 
+/// Opens an in-memory database initialized with a copy of the database image in `data`.
+///
+/// Only `cache_size` and `read_only` are taken from `options`, which may be null. An empty image
+/// creates an empty database.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_set_durability(
-    tx: *mut c_void,
-    durability: redb_durability,
+pub extern "C" fn redb_create_database_from_bytes(
+    data: *const u8,
+    len: usize,
+    options: *const redb_database_options,
+    out: *mut *mut c_void,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+    let image = if len == 0 {
+        &[][..]
+    } else {
+        unsafe {
+            assert!(!data.is_null());
+            std::slice::from_raw_parts(data, len)
+        }
     };
 
-    let redb_durability = match durability {
-        redb_durability::None => redb::Durability::None,
-        redb_durability::Immediate => redb::Durability::Immediate,
-    };
+    let backend = redb::backends::InMemoryBackend::new();
+    if backend.set_len(len as u64).is_err() || backend.write(0, image).is_err() {
+        return REDB_ERROR_STORAGE_ERROR;
+    }
 
-    match tx.set_durability(redb_durability) {
-        Ok(_) => REDB_OK,
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
+    let mut builder = redb::Database::builder();
+    let mut read_only = false;
+    if !options.is_null() {
+        let opts = unsafe { &*options };
+        builder.set_cache_size(opts.cache_size);
+        read_only = opts.read_only;
     }
+
+    let db = match create_with_backend(&builder, backend, read_only) {
+        Ok(db) => db,
+        Err(code) => return code,
+    };
+
+    unsafe {
+        *out = handle::insert(RedbDatabase::new(db, None, read_only));
+    };
+
+    REDB_OK
 }
 
+/// Serializes a compacted copy of the committed contents of `db` into a database image that
+/// `redb_create_database_from_bytes` can open. The image is freed with `redb_free_blob`.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_set_quick_repair(tx: *mut c_void, quick_repair: bool) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+pub extern "C" fn redb_database_to_bytes(db: *mut c_void, out: *mut redb_blob) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = db.database();
+
+    let memory = backends::SharedMemoryBackend::default();
+    let copy = match redb::Database::builder().create_with_backend(memory.clone()) {
+        Ok(copy) => copy,
+        Err(err) => return database_error_code(&err),
     };
 
-    tx.set_quick_repair(quick_repair);
+    let mut copy = match copy_into(&db, copy) {
+        Ok((copy, _)) => copy,
+        Err(code) => return code,
+    };
+
+    loop {
+        match copy.compact() {
+            Ok(true) => continue,
+            Ok(false) => break,
+            Err(_) => return REDB_ERROR_COMPACTION,
+        }
+    }
+
+    // closing the copy writes its allocator state, so the image opens without a repair
+    drop(copy);
+    unsafe {
+        *out = redb_blob::new(memory.take());
+    }
+
     REDB_OK
 }
 
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` or `dest_path` is not valid UTF-8. `dest_path`
+/// may be null to upgrade `path` in place.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_set_two_phase_commit(
-    tx: *mut c_void,
-    two_phase_commit: bool,
+pub extern "C" fn redb_upgrade_database(
+    path: *const c_char,
+    dest_path: *const c_char,
+    out_version: *mut u8,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+    let path = match unsafe { path::from_c_str(path) } {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+    let dest_path = match (!dest_path.is_null()).then(|| unsafe { path::from_c_str(dest_path) }) {
+        Some(Ok(dest_path)) => Some(dest_path),
+        Some(Err(code)) => return code,
+        None => None,
     };
 
-    tx.set_two_phase_commit(two_phase_commit);
-    REDB_OK
+    upgrade::upgrade_database(&path, dest_path.as_deref(), out_version)
 }
 
+/// Like `redb_upgrade_database`, with the paths given as UTF-16 code units.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_open_table(
-    tx: *mut c_void,
-    name: *const c_char,
-    out: *mut *mut c_void,
+pub extern "C" fn redb_upgrade_database_utf16(
+    path: *const u16,
+    path_len: usize,
+    dest_path: *const u16,
+    dest_path_len: usize,
+    out_version: *mut u8,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+    let path = match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => path,
+        Err(code) => return code,
     };
-
-    let c_str = unsafe {
-        assert!(!name.is_null());
-        std::ffi::CStr::from_ptr(name)
+    let dest_path = match (!dest_path.is_null())
+        .then(|| unsafe { path::from_utf16_raw(dest_path, dest_path_len) })
+    {
+        Some(Ok(dest_path)) => Some(dest_path),
+        Some(Err(code)) => return code,
+        None => None,
     };
-    let str_slice = c_str.to_str().unwrap();
 
-    match tx.open_table(TableDefinition::<&[u8], &[u8]>::new(str_slice)) {
-        Ok(table) => {
+    upgrade::upgrade_database(&path, dest_path.as_deref(), out_version)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_compact_database(db: *mut c_void) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    if db.read_only {
+        return REDB_ERROR_READ_ONLY;
+    }
+
+    // open transactions hold their own reference to the database
+    let mut database = db.db.lock().unwrap();
+    let Some(database) = Arc::get_mut(&mut database) else {
+        return REDB_ERROR_COMPACTION;
+    };
+
+    match database.compact() {
+        Ok(_) => REDB_OK,
+        Err(_) => REDB_ERROR_COMPACTION,
+    }
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn redb_database_cache_stats(db: *mut c_void, out: *mut redb_cache_stats) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = db.database();
+
+    let stats = db.cache_stats();
+    unsafe {
+        *out = redb_cache_stats {
+            evictions: stats.evictions(),
+        };
+    }
+
+    REDB_OK
+}
+
+/// Copies a snapshot of `db` into a fresh database file at `path`, written in `format`.
+fn copy_into_file(
+    db: &redb::Database,
+    path: &Path,
+    format: &FileFormat,
+) -> Result<(redb::Database, copy::CopyStats), i32> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|err| file_error_code(&err))?;
+    let backend = FileBackend::new(file).map_err(|err| database_error_code(&err))?;
+
+    let copy = create_in_format(&redb::Database::builder(), backend, format, false)?;
+
+    copy_into(db, copy)
+}
+
+fn copy_into(
+    db: &redb::Database,
+    copy: redb::Database,
+) -> Result<(redb::Database, copy::CopyStats), i32> {
+    let src = db
+        .begin_read()
+        .map_err(|err| transaction_error_code(&err))?;
+
+    let dest = copy
+        .begin_write()
+        .map_err(|err| transaction_error_code(&err))?;
+    let stats = copy::copy_tables(&src, &dest, |_| true).map_err(|err| error_code(&err))?;
+    dest.commit().map_err(|err| error_code(&err.into()))?;
+
+    Ok((copy, stats))
+}
+
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_backup_to(
+    db: *mut c_void,
+    path: *const c_char,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => backup_to(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+/// Like `redb_backup_to`, with the path given as `path_len` UTF-16 code units.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_backup_to_utf16(
+    db: *mut c_void,
+    path: *const u16,
+    path_len: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => backup_to(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+fn backup_to(db: *mut c_void, path: &Path, out_stats: *mut redb_copy_stats) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = db.database();
+
+    let stats = match copy_into_file(&db, path, &FileFormat::Plain) {
+        Ok((_, stats)) => stats,
+        Err(code) => return code,
+    };
+
+    if !out_stats.is_null() {
+        unsafe {
+            *out_stats = redb_copy_stats {
+                tables: stats.tables,
+                entries: stats.entries,
+            };
+        }
+    }
+
+    REDB_OK
+}
+
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_vacuum_into(
+    db: *mut c_void,
+    path: *const c_char,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => vacuum_into(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+/// Like `redb_vacuum_into`, with the path given as `path_len` UTF-16 code units.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_vacuum_into_utf16(
+    db: *mut c_void,
+    path: *const u16,
+    path_len: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => vacuum_into(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+fn vacuum_into(db: *mut c_void, path: &Path, out_stats: *mut redb_copy_stats) -> i32 {
+    let Some(database) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = database.database();
+    let format = database
+        .file
+        .as_ref()
+        .map_or(FileFormat::Plain, |file| file.format.clone());
+
+    let (mut copy, stats) = match copy_into_file(&db, path, &format) {
+        Ok(result) => result,
+        Err(code) => return code,
+    };
+
+    // nothing else can see the copy yet, so compaction cannot be blocked by readers
+    loop {
+        match copy.compact() {
+            Ok(true) => continue,
+            Ok(false) => break,
+            Err(_) => return REDB_ERROR_COMPACTION,
+        }
+    }
+
+    if !out_stats.is_null() {
+        unsafe {
+            *out_stats = redb_copy_stats {
+                tables: stats.tables,
+                entries: stats.entries,
+            };
+        }
+    }
+
+    REDB_OK
+}
+
+/// Replaces the database file at `path` with the one written by `redb_vacuum_into`.
+///
+/// `*db` is closed before the swap and replaced by a handle to the swapped-in file, opened with the
+/// same backend and options as `*db`, so every transaction on it must have been freed first. If
+/// the rename fails or the swapped-in file does not open, both files are put back, the original
+/// is reopened in `*db` and the error code of the failed step returned. Returns
+/// `REDB_ERROR_INVALID_ENCODING` if either path is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_vacuum_swap(
+    db: *mut *mut c_void,
+    path: *const c_char,
+    vacuum_path: *const c_char,
+) -> i32 {
+    let path = match unsafe { path::from_c_str(path) } {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+    match unsafe { path::from_c_str(vacuum_path) } {
+        Ok(vacuum_path) => vacuum_swap(db, &path, &vacuum_path),
+        Err(code) => code,
+    }
+}
+
+/// Like `redb_vacuum_swap`, with the paths given as UTF-16 code units.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_vacuum_swap_utf16(
+    db: *mut *mut c_void,
+    path: *const u16,
+    path_len: usize,
+    vacuum_path: *const u16,
+    vacuum_path_len: usize,
+) -> i32 {
+    let path = match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+    match unsafe { path::from_utf16_raw(vacuum_path, vacuum_path_len) } {
+        Ok(vacuum_path) => vacuum_swap(db, &path, &vacuum_path),
+        Err(code) => code,
+    }
+}
+
+fn vacuum_swap(db: *mut *mut c_void, path: &Path, vacuum_path: &Path) -> i32 {
+    let db_handle = unsafe {
+        assert!(!db.is_null());
+        *db
+    };
+    match handle::get::<RedbDatabase>(db_handle) {
+        Some(database) if database.read_only => return REDB_ERROR_READ_ONLY,
+        Some(_) => {}
+        None => return REDB_ERROR_INVALID_HANDLE,
+    }
+    // the file cannot be reopened while a call or an open transaction keeps the database alive
+    let RedbDatabase {
+        db: shared, file, ..
+    } = match handle::take::<RedbDatabase>(db_handle) {
+        Ok(database) => database,
+        Err(handle::Exclusive::Invalid) => return REDB_ERROR_INVALID_HANDLE,
+        Err(handle::Exclusive::Shared) => return REDB_ERROR_READ_TRANSACTION_STILL_IN_USE,
+    };
+    let original = match Arc::try_unwrap(shared.into_inner().unwrap()) {
+        Ok(original) => original,
+        Err(shared) => unsafe {
+            *db = handle::insert(RedbDatabase {
+                db: Mutex::new(shared),
+                file,
+                read_only: false,
+            });
+            return REDB_ERROR_READ_TRANSACTION_STILL_IN_USE;
+        },
+    };
+    // a file has to be closed before it is replaced; a database without one is kept until the
+    // swapped-in file has opened, and is reopened from `path` as a plain file
+    let original = match file {
+        Some(_) => {
+            drop(original);
+            None
+        }
+        None => Some(original),
+    };
+    unsafe {
+        *db = std::ptr::null_mut();
+    }
+
+    let options = file.clone().unwrap_or_default();
+    let (reopened, file, code) = match swap_files(path, vacuum_path, &options) {
+        Ok(swapped) => (Ok(swapped), Some(options), REDB_OK),
+        // the caller keeps the database it had
+        Err(code) => match original {
+            Some(original) => (Ok(original), file, code),
+            None => (open_file_database(path, &options), file, code),
+        },
+    };
+    match reopened {
+        Ok(reopened) => unsafe {
+            *db = handle::insert(RedbDatabase::new(reopened, file, false));
+            code
+        },
+        Err(reopen_code) => reopen_code,
+    }
+}
+
+/// Moves the file at `vacuum_path` to `path` and opens it. The file it replaces is set aside until
+/// then, so both are back where they were if the move or the open fails.
+fn swap_files(
+    path: &Path,
+    vacuum_path: &Path,
+    options: &FileOptions,
+) -> Result<redb::Database, i32> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".swap");
+    let backup = PathBuf::from(backup);
+
+    let replaces = match std::fs::rename(path, &backup) {
+        Ok(()) => true,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+        Err(err) => return Err(file_error_code(&err)),
+    };
+    let restore = || {
+        if replaces {
+            let _ = std::fs::rename(&backup, path);
+        }
+    };
+
+    if let Err(err) = std::fs::rename(vacuum_path, path) {
+        restore();
+        return Err(file_error_code(&err));
+    }
+    match open_file_database(path, options) {
+        Ok(db) => {
+            if replaces {
+                let _ = std::fs::remove_file(&backup);
+            }
+            Ok(db)
+        }
+        Err(code) => {
+            let _ = std::fs::rename(path, vacuum_path);
+            restore();
+            Err(code)
+        }
+    }
+}
+
+/// Writes a compacted copy of the committed contents of `db` to a new database file at `path`,
+/// typically to save an `InMemory` database.
+///
+/// The copy is written to a temporary file next to `path` and renamed over it once complete, so
+/// `path` never holds a partially written database. `db` stays open on its original backend.
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_save_as(
+    db: *mut c_void,
+    path: *const c_char,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => save_as(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+/// Like `redb_save_as`, with the path given as `path_len` UTF-16 code units.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_save_as_utf16(
+    db: *mut c_void,
+    path: *const u16,
+    path_len: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => save_as(db, &path, out_stats),
+        Err(code) => code,
+    }
+}
+
+fn save_as(db: *mut c_void, path: &Path, out_stats: *mut redb_copy_stats) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = db.database();
+
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = PathBuf::from(temp_path);
+    let stats = match save_to_temp(&db, &temp_path) {
+        Ok(stats) => stats,
+        Err(code) => {
+            let _ = std::fs::remove_file(&temp_path);
+            return code;
+        }
+    };
+
+    if let Err(err) = std::fs::rename(&temp_path, path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return file_error_code(&err);
+    }
+    sync_parent_dir(path);
+
+    if !out_stats.is_null() {
+        unsafe {
+            *out_stats = redb_copy_stats {
+                tables: stats.tables,
+                entries: stats.entries,
+            };
+        }
+    }
+
+    REDB_OK
+}
+
+fn save_to_temp(db: &redb::Database, temp_path: &Path) -> Result<copy::CopyStats, i32> {
+    let (mut copy, stats) = copy_into_file(db, temp_path, &FileFormat::Plain)?;
+
+    loop {
+        match copy.compact() {
+            Ok(true) => continue,
+            Ok(false) => break,
+            Err(_) => return Err(REDB_ERROR_COMPACTION),
+        }
+    }
+
+    // closed before the rename so the lock is released and the file opens without a repair
+    drop(copy);
+    Ok(stats)
+}
+
+/// Makes a rename into the parent directory durable. Best effort, as not every platform can open
+/// a directory.
+fn sync_parent_dir(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_database(db: *mut c_void) {
+    drop(handle::remove::<RedbDatabase>(db));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_table(table: *mut c_void) {
+    drop(handle::remove::<RedbTable>(table));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_readonly_table(table: *mut c_void) {
+    drop(handle::remove::<RedbReadOnlyTable>(table));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_write_transaction(tx: *mut c_void) {
+    drop(handle::remove::<RedbWriteTransaction>(tx));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_read_transaction(tx: *mut c_void) {
+    drop(handle::remove::<RedbReadTransaction>(tx));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_savepoint(savepoint: *mut c_void) {
+    drop(handle::remove::<redb::Savepoint>(savepoint));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_string(s: *mut c_char) {
+    if s.is_null() {
+        return;
+    }
+    unsafe {
+        drop(std::ffi::CString::from_raw(s));
+    }
+}
+
+/// Frees a blob written by the library and resets it to empty, so freeing it again does nothing.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_blob(blob: *mut redb_blob) {
+    if blob.is_null() {
+        return;
+    }
+    unsafe {
+        drop(blob.replace(redb_blob::EMPTY).into_vec());
+    }
+}
+
+/// Frees an array written by the library and resets it to empty, so freeing it again does nothing.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_u64_array(array: *mut redb_u64_array) {
+    if array.is_null() {
+        return;
+    }
+    unsafe {
+        drop(array.replace(redb_u64_array::EMPTY).into_vec());
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_begin_write(db: *mut c_void, out: *mut *mut c_void) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    if db.read_only {
+        return REDB_ERROR_READ_ONLY;
+    }
+
+    let db = db.database();
+    match db.begin_write() {
+        Ok(tx) => {
+            unsafe {
+                *out = handle::insert(RedbWriteTransaction { tx, _db: db });
+            };
+            REDB_OK
+        }
+        Err(err) => transaction_error_code(&err),
+    }
+}
+
+/// Applies the steps of `migrations` newer than the database schema version, in order, in a single
+/// write transaction, then records the last step's version. Steps must have strictly increasing
+/// versions. If a step fails its error code is returned, the transaction is aborted, and tables
+/// the step left open are closed. Without any steps the database is left as it is; otherwise
+/// `REDB_ERROR_SCHEMA_VERSION_TOO_NEW` is returned if it is already newer than the last step.
+/// `out_version` receives the schema version the database is at afterwards.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_migrate(
+    db: *mut c_void,
+    migrations: *const redb_migration,
+    migration_count: usize,
+    out_version: *mut u32,
+) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    if db.read_only {
+        return REDB_ERROR_READ_ONLY;
+    }
+
+    let migrations = if migration_count == 0 {
+        &[][..]
+    } else {
+        unsafe {
+            assert!(!migrations.is_null());
+            std::slice::from_raw_parts(migrations, migration_count)
+        }
+    };
+    if !migrations.windows(2).all(|w| w[0].version < w[1].version) {
+        return REDB_ERROR_INVALID_MIGRATION;
+    }
+
+    let db = db.database();
+    let tx = match db.begin_write() {
+        Ok(tx) => tx,
+        Err(err) => return transaction_error_code(&err),
+    };
+    let current = match schema::version_for_write(&tx) {
+        Ok(version) => version,
+        Err(err) => return error_code(&err),
+    };
+    unsafe {
+        assert!(!out_version.is_null());
+        *out_version = current;
+    }
+
+    let Some(target) = migrations.last().map(|step| step.version) else {
+        return REDB_OK;
+    };
+    if current > target {
+        return REDB_ERROR_SCHEMA_VERSION_TOO_NEW;
+    }
+    if current == target {
+        return REDB_OK;
+    }
+
+    // steps get the same kind of handle `redb_begin_write` hands out
+    let tx_handle = handle::insert(RedbWriteTransaction { tx, _db: db });
+    let shared = handle::get::<RedbWriteTransaction>(tx_handle).map(|tx| Arc::as_ptr(&tx));
+    for step in migrations.iter().filter(|step| step.version > current) {
+        let code = (step.migrate)(step.user_data, tx_handle);
+        if code != REDB_OK {
+            abort_migration(tx_handle, shared);
+            return code;
+        }
+    }
+    let RedbWriteTransaction { tx, _db } = match take_write_transaction(tx_handle) {
+        Ok(tx) => tx,
+        Err(code) => {
+            // a step freed the transaction or left a table open
+            abort_migration(tx_handle, shared);
+            return code;
+        }
+    };
+
+    if let Err(err) = schema::set_version(&tx, target) {
+        return error_code(&err);
+    }
+    match tx.commit() {
+        Ok(_) => {
+            unsafe { *out_version = target };
+            REDB_OK
+        }
+        Err(err) => commit_error_code(err),
+    }
+}
+
+/// Frees the migration transaction and every table still open on it, which aborts the
+/// transaction so it does not keep blocking writers.
+fn abort_migration(tx: *mut c_void, shared: Option<*const RedbWriteTransaction>) {
+    drop(handle::remove::<RedbWriteTransaction>(tx));
+    drop(handle::remove_matching(|table: &RedbTable| {
+        Some(Arc::as_ptr(&table.tx)) == shared
+    }));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_set_durability(
+    tx: *mut c_void,
+    durability: redb_durability,
+) -> i32 {
+    let redb_durability = match durability {
+        redb_durability::None => redb::Durability::None,
+        redb_durability::Immediate => redb::Durability::Immediate,
+    };
+
+    match update_write_transaction(tx, |tx| tx.set_durability(redb_durability)) {
+        Ok(Ok(_)) => REDB_OK,
+        Ok(Err(_)) => REDB_ERROR_STORAGE_ERROR,
+        Err(code) => code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_set_quick_repair(tx: *mut c_void, quick_repair: bool) -> i32 {
+    match update_write_transaction(tx, |tx| tx.set_quick_repair(quick_repair)) {
+        Ok(()) => REDB_OK,
+        Err(code) => code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_set_two_phase_commit(
+    tx: *mut c_void,
+    two_phase_commit: bool,
+) -> i32 {
+    match update_write_transaction(tx, |tx| tx.set_two_phase_commit(two_phase_commit)) {
+        Ok(()) => REDB_OK,
+        Err(code) => code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_open_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    open_write_table(&tx, str_slice, None, out)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_open_table_with_codec(
+    tx: *mut c_void,
+    name: *const c_char,
+    options: *const redb_table_codec_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    let codec = match value_codec(options) {
+        Ok(codec) => codec,
+        Err(code) => return code,
+    };
+
+    open_write_table(&tx, str_slice, Some(codec), out)
+}
+
+fn open_write_table(
+    tx: &Arc<RedbWriteTransaction>,
+    name: &str,
+    codec: Option<Arc<codec::ValueCodec>>,
+    out: *mut *mut c_void,
+) -> i32 {
+    if schema::is_reserved(name) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    // the table holds `tx`, which keeps the transaction it borrows alive
+    let transaction: &'static redb::WriteTransaction = unsafe { &*Arc::as_ptr(tx) };
+    match transaction.open_table(TableDefinition::<&[u8], &[u8]>::new(name)) {
+        Ok(mut table) => {
+            let codec = match write_table_codec(transaction, name, &mut table, codec) {
+                Ok(codec) => codec,
+                Err(code) => return code,
+            };
+            unsafe {
+                *out = handle::insert(RedbTable {
+                    table: Mutex::new(WriteTable::Bytes(table)),
+                    codec,
+                    tx: tx.clone(),
+                });
+            };
+            REDB_OK
+        }
+        Err(err) => table_error_code(&err),
+    }
+}
+
+/// The codec values of `name` go through. A table is switched to the codec format the first time
+/// it is opened with a compressing codec, encoding the values it already holds; until then values
+/// are written as they are. Once switched, opens without a codec go through `stored_codec`.
+fn write_table_codec(
+    tx: &redb::WriteTransaction,
+    name: &str,
+    table: &mut redb::Table<&'static [u8], &'static [u8]>,
+    codec: Option<Arc<codec::ValueCodec>>,
+) -> Result<Option<Arc<codec::ValueCodec>>, i32> {
+    match schema::has_codec_for_write(tx, name) {
+        Ok(true) => return codec.map_or_else(stored_codec, Ok).map(Some),
+        Ok(false) => {}
+        Err(err) => return Err(error_code(&err)),
+    }
+    let Some(codec) = codec.filter(|codec| codec.compresses()) else {
+        return Ok(None);
+    };
+
+    let entries = table
+        .iter()
+        .and_then(|iter| {
+            iter.map(|entry| entry.map(|(k, v)| (k.value().to_vec(), v.value().to_vec())))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|err| storage_error_code(&err))?;
+    for (key, value) in entries {
+        let encoded = codec.encode(&value).map_err(|_| REDB_ERROR_CODEC)?;
+        table
+            .insert(key.as_slice(), encoded.as_slice())
+            .map_err(|err| storage_error_code(&err))?;
+    }
+    schema::set_codec(tx, name).map_err(|err| error_code(&err))?;
+
+    Ok(Some(codec))
+}
+
+/// Opens a table whose key and value types are given by descriptors. The handle is used with the
+/// same exports as any other table, with keys and values in redb's encoding for those types.
+/// The first write open records the redb type names and the `redb` encoding as the table's schema;
+/// later opens with other types fail with `REDB_ERROR_SCHEMA_MISMATCH`.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_open_typed_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    key_type: *const redb_type_descriptor,
+    value_type: *const redb_type_descriptor,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    match typed_schema(key_type, value_type) {
+        Ok(schema) => open_typed_write_table(&tx, str_slice, schema, out),
+        Err(code) => code,
+    }
+}
+
+fn open_typed_write_table(
+    tx: &Arc<RedbWriteTransaction>,
+    name: &str,
+    schema: Arc<typed::TypedSchema>,
+    out: *mut *mut c_void,
+) -> i32 {
+    if schema::is_reserved(name) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    // the table holds `tx`, which keeps the transaction it borrows alive
+    let transaction: &'static redb::WriteTransaction = unsafe { &*Arc::as_ptr(tx) };
+    let (key_type, value_type) = schema.type_names();
+    let recorded = match schema::get_for_write(transaction, name) {
+        Ok(Some(recorded)) if !recorded.has_types(key_type, value_type) => {
+            return REDB_ERROR_SCHEMA_MISMATCH;
+        }
+        Ok(recorded) => recorded,
+        Err(err) => return error_code(&err),
+    };
+
+    let definition = TableDefinition::<typed::TypedKey, typed::TypedValue>::new(name);
+    match typed::with_schema(&schema, || transaction.open_table(definition)) {
+        Ok(table) => {
+            if recorded.is_none() {
+                let record = schema::TableSchema {
+                    key_type: key_type.to_owned(),
+                    value_type: value_type.to_owned(),
+                    encoding: schema::TYPED_ENCODING.to_owned(),
+                    version: 0,
+                };
+                if let Err(err) = schema::set(transaction, name, &record) {
+                    return error_code(&err);
+                }
+            }
+            unsafe {
+                *out = handle::insert(RedbTable {
+                    table: Mutex::new(WriteTable::Typed(table, schema)),
+                    codec: None,
+                    tx: tx.clone(),
+                });
+            };
+            REDB_OK
+        }
+        Err(err) => table_error_code(&err),
+    }
+}
+
+/// Registers the ordering of keys of the `Custom` type named `type_name`. The comparator must
+/// order keys exactly as the type's Rust `Key::compare` does if the table is shared with Rust
+/// programs, and must never change for a name once a table uses it.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_register_key_comparator(
+    type_name: *const c_char,
+    comparator: *const redb_key_comparator,
+) -> i32 {
+    let c_str = unsafe {
+        assert!(!type_name.is_null());
+        std::ffi::CStr::from_ptr(type_name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    let comparator = unsafe {
+        assert!(!comparator.is_null());
+        *comparator
+    };
+
+    comparator::register(str_slice, comparator);
+    REDB_OK
+}
+
+/// Removes the comparator registered for `type_name`. Tables already open keep using it.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_unregister_key_comparator(type_name: *const c_char) -> i32 {
+    let c_str = unsafe {
+        assert!(!type_name.is_null());
+        std::ffi::CStr::from_ptr(type_name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    if comparator::unregister(str_slice) {
+        REDB_OK
+    } else {
+        REDB_ERROR_KEY_NOT_FOUND
+    }
+}
+
+/// Opens a table keyed by a redb integer type with `&[u8]` values, matching a Rust
+/// `TableDefinition<u64, &[u8]>` and so on. Keys sort numerically; use the `_u64`, `_i64`, `_u32`
+/// and `_i32` insert, get and range exports with it.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_open_numeric_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    key_type: redb_numeric_key,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    open_typed_write_table(&tx, str_slice, numeric_schema(key_type), out)
+}
+
+/// Opens a table keyed by a tuple of `component_count` component types with `&[u8]` values,
+/// matching a Rust `TableDefinition<(u32, i64, [u8; 16]), &[u8]>` and so on. Build keys with
+/// `redb_encode_composite_key` and scan everything under a leading set of components with
+/// `redb_range_prefix`.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_open_composite_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    components: *const redb_type_descriptor,
+    component_count: usize,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    match composite_schema(components, component_count) {
+        Ok(schema) => open_typed_write_table(&tx, str_slice, schema, out),
+        Err(code) => code,
+    }
+}
+
+fn composite_key_type(
+    components: *const redb_type_descriptor,
+    component_count: usize,
+) -> redb_type_descriptor {
+    redb_type_descriptor {
+        kind: redb_type_kind::Tuple,
+        elements: components,
+        element_count: component_count,
+        array_len: 0,
+        name: std::ptr::null(),
+    }
+}
+
+fn composite_schema(
+    components: *const redb_type_descriptor,
+    component_count: usize,
+) -> Result<Arc<typed::TypedSchema>, i32> {
+    let value_type = redb_type_descriptor {
+        kind: redb_type_kind::Bytes,
+        ..composite_key_type(std::ptr::null(), 0)
+    };
+    typed_schema(
+        &composite_key_type(components, component_count),
+        &value_type,
+    )
+}
+
+fn composite_elements(
+    components: *const redb_type_descriptor,
+    component_count: usize,
+) -> Result<Vec<typed::DynType>, i32> {
+    let key_type = composite_key_type(components, component_count);
+    match unsafe { typed::DynType::from_descriptor(&key_type) } {
+        Ok(typed::DynType::Tuple(elements)) => Ok(elements),
+        _ => Err(REDB_ERROR_UNSUPPORTED_TYPE),
+    }
+}
+
+fn numeric_key_type(key_type: redb_numeric_key) -> typed::DynType {
+    match key_type {
+        redb_numeric_key::U64 => typed::DynType::Unsigned(8),
+        redb_numeric_key::I64 => typed::DynType::Signed(8),
+        redb_numeric_key::U32 => typed::DynType::Unsigned(4),
+        redb_numeric_key::I32 => typed::DynType::Signed(4),
+    }
+}
+
+fn numeric_schema(key_type: redb_numeric_key) -> Arc<typed::TypedSchema> {
+    let schema = typed::TypedSchema::new(numeric_key_type(key_type), typed::DynType::Bytes);
+    Arc::new(schema.expect("integers are valid keys"))
+}
+
+fn typed_schema(
+    key_type: *const redb_type_descriptor,
+    value_type: *const redb_type_descriptor,
+) -> Result<Arc<typed::TypedSchema>, i32> {
+    let schema = unsafe {
+        assert!(!key_type.is_null() && !value_type.is_null());
+        typed::DynType::from_descriptor(key_type).and_then(|key| {
+            typed::TypedSchema::new(key, typed::DynType::from_descriptor(value_type)?)
+        })
+    };
+    schema
+        .map(Arc::new)
+        .map_err(|_| REDB_ERROR_UNSUPPORTED_TYPE)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_delete_table(tx: *mut c_void, name: *const c_char) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    match tx.delete_table(TableDefinition::<&[u8], &[u8]>::new(str_slice)) {
+        Ok(_) => match schema::remove(&tx, str_slice) {
+            Ok(()) => REDB_OK,
+            Err(err) => error_code(&err),
+        },
+        Err(err) => table_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_rename_table(
+    tx: *mut c_void,
+    old_name: *const c_char,
+    new_name: *const c_char,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let old_c_str = unsafe {
+        assert!(!old_name.is_null());
+        std::ffi::CStr::from_ptr(old_name)
+    };
+    let old_str_slice = old_c_str.to_str().unwrap();
+
+    let new_c_str = unsafe {
+        assert!(!new_name.is_null());
+        std::ffi::CStr::from_ptr(new_name)
+    };
+    let new_str_slice = new_c_str.to_str().unwrap();
+    if schema::is_reserved(old_str_slice) || schema::is_reserved(new_str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    match tx.rename_table(
+        TableDefinition::<&[u8], &[u8]>::new(old_str_slice),
+        TableDefinition::<&[u8], &[u8]>::new(new_str_slice),
+    ) {
+        Ok(_) => match schema::rename(&tx, old_str_slice, new_str_slice) {
+            Ok(()) => REDB_OK,
+            Err(err) => error_code(&err),
+        },
+        Err(err) => table_error_code(&err),
+    }
+}
+
+/// Records the key type, value type, encoding and version of table `name` in the database's
+/// reserved `$redb.schema` table, replacing any earlier record. Deleting or renaming the table
+/// through `redb_write_tx_delete_table` and `redb_write_tx_rename_table` updates the record.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_set_table_schema(
+    tx: *mut c_void,
+    name: *const c_char,
+    schema: *const redb_table_schema,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    let schema = unsafe {
+        assert!(!schema.is_null());
+        &*schema
+    };
+    let field = |s: *const c_char| unsafe {
+        assert!(!s.is_null());
+        std::ffi::CStr::from_ptr(s)
+            .to_str()
+            .map(str::to_owned)
+            .map_err(|_| REDB_ERROR_INVALID_ENCODING)
+    };
+    let schema = match (
+        field(schema.key_type),
+        field(schema.value_type),
+        field(schema.encoding),
+    ) {
+        (Ok(key_type), Ok(value_type), Ok(encoding)) => schema::TableSchema {
+            key_type,
+            value_type,
+            encoding,
+            version: schema.version,
+        },
+        _ => return REDB_ERROR_INVALID_ENCODING,
+    };
+
+    match schema::set(&tx, str_slice, &schema) {
+        Ok(()) => REDB_OK,
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Reads the schema recorded for table `name`, or returns `REDB_ERROR_KEY_NOT_FOUND`.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_get_table_schema(
+    tx: *mut c_void,
+    name: *const c_char,
+    out: *mut redb_table_schema,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    write_table_schema(schema::get_for_write(&tx, str_slice), out)
+}
+
+/// Checks table `name` against the schema recorded for it. Null strings in `expected` are not
+/// checked. Returns `REDB_ERROR_KEY_NOT_FOUND` when nothing is recorded and
+/// `REDB_ERROR_SCHEMA_MISMATCH` when the record differs.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_validate_table_schema(
+    tx: *mut c_void,
+    name: *const c_char,
+    expected: *const redb_table_schema,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    validate_table_schema(schema::get_for_write(&tx, str_slice), expected)
+}
+
+fn write_table_schema(
+    schema: Result<Option<schema::TableSchema>, redb::Error>,
+    out: *mut redb_table_schema,
+) -> i32 {
+    match schema {
+        Ok(Some(schema)) => {
+            // records written by Rust programs may contain NUL, which read back as empty strings
+            let field = |s: String| {
+                std::ffi::CString::new(s)
+                    .unwrap_or_default()
+                    .into_raw()
+                    .cast_const()
+            };
+            unsafe {
+                assert!(!out.is_null());
+                *out = redb_table_schema {
+                    key_type: field(schema.key_type),
+                    value_type: field(schema.value_type),
+                    encoding: field(schema.encoding),
+                    version: schema.version,
+                };
+            }
+            REDB_OK
+        }
+        Ok(None) => REDB_ERROR_KEY_NOT_FOUND,
+        Err(err) => error_code(&err),
+    }
+}
+
+fn validate_table_schema(
+    schema: Result<Option<schema::TableSchema>, redb::Error>,
+    expected: *const redb_table_schema,
+) -> i32 {
+    let expected = unsafe {
+        assert!(!expected.is_null());
+        &*expected
+    };
+    let field = |s: *const c_char| unsafe {
+        (!s.is_null())
+            .then(|| std::ffi::CStr::from_ptr(s).to_str())
+            .transpose()
+    };
+    let filter = match (
+        field(expected.key_type),
+        field(expected.value_type),
+        field(expected.encoding),
+    ) {
+        (Ok(key_type), Ok(value_type), Ok(encoding)) => schema::SchemaFilter {
+            key_type,
+            value_type,
+            encoding,
+            version: expected.version,
+        },
+        _ => return REDB_ERROR_INVALID_ENCODING,
+    };
+
+    match schema {
+        Ok(Some(schema)) if schema.matches(&filter) => REDB_OK,
+        Ok(Some(_)) => REDB_ERROR_SCHEMA_MISMATCH,
+        Ok(None) => REDB_ERROR_KEY_NOT_FOUND,
+        Err(err) => error_code(&err),
+    }
+}
+
+fn write_transaction_error_code(err: handle::Exclusive) -> i32 {
+    match err {
+        handle::Exclusive::Invalid => REDB_ERROR_INVALID_HANDLE,
+        // tables opened from the transaction borrow it
+        handle::Exclusive::Shared => REDB_ERROR_TABLE_ALREADY_OPEN,
+    }
+}
+
+/// Removes a write transaction handle so it can be committed or aborted, unless a table opened
+/// from it is still open.
+fn take_write_transaction(tx: *mut c_void) -> Result<RedbWriteTransaction, i32> {
+    handle::take::<RedbWriteTransaction>(tx).map_err(write_transaction_error_code)
+}
+
+/// Calls `f` on the write transaction behind `tx`, unless a table opened from it is still open.
+fn update_write_transaction<R>(
+    tx: *mut c_void,
+    f: impl FnOnce(&mut redb::WriteTransaction) -> R,
+) -> Result<R, i32> {
+    handle::update(tx, |tx: &mut RedbWriteTransaction| f(&mut tx.tx))
+        .map_err(write_transaction_error_code)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_abort(tx: *mut c_void) -> i32 {
+    let RedbWriteTransaction { tx, _db } = match take_write_transaction(tx) {
+        Ok(tx) => tx,
+        Err(code) => return code,
+    };
+
+    match tx.abort() {
+        Ok(_) => REDB_OK,
+        Err(_) => REDB_ERROR_STORAGE_ERROR,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_commit(tx: *mut c_void) -> i32 {
+    let RedbWriteTransaction { tx, _db } = match take_write_transaction(tx) {
+        Ok(tx) => tx,
+        Err(code) => return code,
+    };
+
+    match tx.commit() {
+        Ok(_) => REDB_OK,
+        Err(err) => commit_error_code(err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_ephemeral_savepoint(tx: *mut c_void, out: *mut *mut c_void) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match tx.ephemeral_savepoint() {
+        Ok(savepoint) => {
+            unsafe {
+                *out = handle::insert(savepoint);
+            };
+            REDB_OK
+        }
+        Err(_) => REDB_ERROR_STORAGE_ERROR,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_restore_savepoint(tx: *mut c_void, savepoint: *mut c_void) -> i32 {
+    let Some(savepoint) = handle::get::<redb::Savepoint>(savepoint) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match update_write_transaction(tx, |tx| tx.restore_savepoint(&savepoint)) {
+        Ok(Ok(_)) => REDB_OK,
+        Ok(Err(_)) => REDB_ERROR_STORAGE_ERROR,
+        Err(code) => code,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_persistent_savepoint(tx: *mut c_void, out: *mut u64) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match tx.persistent_savepoint() {
+        Ok(id) => {
+            unsafe {
+                *out = id;
+            };
+            REDB_OK
+        }
+        Err(err) => savepoint_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_get_persistent_savepoint(
+    tx: *mut c_void,
+    id: u64,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match tx.get_persistent_savepoint(id) {
+        Ok(savepoint) => {
+            unsafe {
+                *out = handle::insert(savepoint);
+            };
+            REDB_OK
+        }
+        Err(err) => savepoint_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_delete_persistent_savepoint(
+    tx: *mut c_void,
+    id: u64,
+    out: *mut bool,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match tx.delete_persistent_savepoint(id) {
+        Ok(ret) => {
+            unsafe {
+                *out = ret;
+            };
+            REDB_OK
+        }
+        Err(err) => savepoint_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_lists_persistent_savepoint(
+    tx: *mut c_void,
+    out: *mut redb_u64_array,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let iter = match tx.list_persistent_savepoints() {
+        Ok(iter) => iter,
+        Err(_) => return REDB_ERROR_STORAGE_ERROR,
+    };
+
+    unsafe {
+        assert!(!out.is_null());
+        *out = redb_u64_array::new(iter.collect());
+    }
+
+    REDB_OK
+}
+
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` or a table name is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_import(
+    tx: *mut c_void,
+    path: *const c_char,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => write_tx_import(tx, &path, table_names, table_count, out_stats),
+        Err(code) => code,
+    }
+}
+
+/// Like `redb_write_tx_import`, with the path given as `path_len` UTF-16 code units.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_write_tx_import_utf16(
+    tx: *mut c_void,
+    path: *const u16,
+    path_len: usize,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => write_tx_import(tx, &path, table_names, table_count, out_stats),
+        Err(code) => code,
+    }
+}
+
+fn write_tx_import(
+    tx: *mut c_void,
+    path: &Path,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbWriteTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let filter = match table_name_filter(table_names, table_count) {
+        Ok(filter) => filter,
+        Err(code) => return code,
+    };
+
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => return file_error_code(&err),
+    };
+
+    match dump::import(&tx, &mut std::io::BufReader::new(file), filter) {
+        Ok(stats) => {
+            if !out_stats.is_null() {
+                unsafe {
+                    *out_stats = redb_copy_stats {
+                        tables: stats.tables,
+                        entries: stats.entries,
+                    };
+                }
+            }
+            REDB_OK
+        }
+        Err(err) => dump_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_insert(
+    table: *mut c_void,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    let Some(table) = handle::get::<RedbTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let key_slice = unsafe {
+        assert!(!key.is_null());
+        std::slice::from_raw_parts(key, key_len)
+    };
+
+    let value_slice = unsafe {
+        assert!(!value.is_null());
+        std::slice::from_raw_parts(value, value_len)
+    };
+
+    let encoded;
+    let value_slice = match &table.codec {
+        Some(codec) => match codec.encode(value_slice) {
+            Ok(value) => {
+                encoded = value;
+                encoded.as_slice()
+            }
+            Err(_) => return REDB_ERROR_CODEC,
+        },
+        None => value_slice,
+    };
+
+    let result = match &mut *table.table.lock().unwrap() {
+        WriteTable::Bytes(table) => table.insert(key_slice, value_slice).map(drop),
+        WriteTable::Typed(table, schema) => {
+            if !schema.key.validate(key_slice) || !schema.value.validate(value_slice) {
+                return REDB_ERROR_INVALID_ENCODING;
+            }
+            typed::with_schema(schema, || table.insert(key_slice, value_slice).map(drop))
+        }
+    };
+
+    match result {
+        Ok(()) => REDB_OK,
+        Err(err) => storage_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_begin_read(db: *mut c_void, out: *mut *mut c_void) -> i32 {
+    let Some(db) = handle::get::<RedbDatabase>(db) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let db = db.database();
+    match db.begin_read() {
+        Ok(tx) => {
+            unsafe {
+                *out = handle::insert(RedbReadTransaction { tx, _db: db });
+            };
+            REDB_OK
+        }
+        Err(err) => transaction_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_open_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    open_readonly_table(&tx, str_slice, None, out)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_open_table_with_codec(
+    tx: *mut c_void,
+    name: *const c_char,
+    options: *const redb_table_codec_options,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    let codec = match value_codec(options) {
+        Ok(codec) => codec,
+        Err(code) => return code,
+    };
+
+    open_readonly_table(&tx, str_slice, Some(codec), out)
+}
+
+fn open_readonly_table(
+    tx: &Arc<RedbReadTransaction>,
+    name: &str,
+    codec: Option<Arc<codec::ValueCodec>>,
+    out: *mut *mut c_void,
+) -> i32 {
+    if schema::is_reserved(name) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    // values of tables that never switched to the codec format are read as they are
+    let codec = match schema::has_codec(tx, name) {
+        Ok(true) => match codec.map_or_else(stored_codec, Ok) {
+            Ok(codec) => Some(codec),
+            Err(code) => return code,
+        },
+        Ok(false) => None,
+        Err(err) => return error_code(&err),
+    };
+    match tx.open_table(TableDefinition::<&[u8], &[u8]>::new(name)) {
+        Ok(table) => {
+            unsafe {
+                *out = handle::insert(RedbReadOnlyTable {
+                    table: ReadTable::Bytes(table),
+                    codec,
+                    tx: tx.clone(),
+                });
+            };
+            REDB_OK
+        }
+        Err(err) => table_error_code(&err),
+    }
+}
+
+/// Read-only counterpart of `redb_write_tx_open_typed_table`.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_open_typed_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    key_type: *const redb_type_descriptor,
+    value_type: *const redb_type_descriptor,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    match typed_schema(key_type, value_type) {
+        Ok(schema) => open_typed_readonly_table(&tx, str_slice, schema, out),
+        Err(code) => code,
+    }
+}
+
+/// Read-only counterpart of `redb_write_tx_open_numeric_table`.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_open_numeric_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    key_type: redb_numeric_key,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    open_typed_readonly_table(&tx, str_slice, numeric_schema(key_type), out)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_open_composite_table(
+    tx: *mut c_void,
+    name: *const c_char,
+    components: *const redb_type_descriptor,
+    component_count: usize,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+
+    match composite_schema(components, component_count) {
+        Ok(schema) => open_typed_readonly_table(&tx, str_slice, schema, out),
+        Err(code) => code,
+    }
+}
+
+fn open_typed_readonly_table(
+    tx: &Arc<RedbReadTransaction>,
+    name: &str,
+    schema: Arc<typed::TypedSchema>,
+    out: *mut *mut c_void,
+) -> i32 {
+    if schema::is_reserved(name) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    let (key_type, value_type) = schema.type_names();
+    match schema::get(tx, name) {
+        Ok(Some(recorded)) if !recorded.has_types(key_type, value_type) => {
+            return REDB_ERROR_SCHEMA_MISMATCH;
+        }
+        Ok(_) => {}
+        Err(err) => return error_code(&err),
+    }
+
+    let definition = TableDefinition::<typed::TypedKey, typed::TypedValue>::new(name);
+    match typed::with_schema(&schema, || tx.open_table(definition)) {
+        Ok(table) => {
             unsafe {
-                *out = Box::into_raw(Box::new(table)) as *mut c_void;
+                *out = handle::insert(RedbReadOnlyTable {
+                    table: ReadTable::Typed(table, schema),
+                    codec: None,
+                    tx: tx.clone(),
+                });
             };
             REDB_OK
         }
@@ -369,11 +2504,87 @@ pub extern "C" fn redb_write_tx_open_table(
     }
 }
 
+fn value_codec(options: *const redb_table_codec_options) -> Result<Arc<codec::ValueCodec>, i32> {
+    let opts = unsafe {
+        assert!(!options.is_null());
+        &*options
+    };
+
+    let codec = match opts.codec {
+        redb_value_codec::None => codec::Codec::None,
+        redb_value_codec::Lz4 => codec::Codec::Lz4,
+        redb_value_codec::Zstd => codec::Codec::Zstd {
+            level: opts.level,
+            dictionary: if opts.dictionary.is_null() {
+                Vec::new()
+            } else {
+                unsafe { std::slice::from_raw_parts(opts.dictionary, opts.dictionary_len) }.to_vec()
+            },
+        },
+    };
+
+    codec::ValueCodec::new(codec)
+        .map(Arc::new)
+        .map_err(|_| REDB_ERROR_CODEC)
+}
+
+/// The codec a table in the codec format is opened with when the host gives none: values are
+/// decoded with the codec that wrote them and new ones are written stored.
+fn stored_codec() -> Result<Arc<codec::ValueCodec>, i32> {
+    codec::ValueCodec::new(codec::Codec::None)
+        .map(Arc::new)
+        .map_err(|_| REDB_ERROR_CODEC)
+}
+
+/// Reads the database schema version recorded by `redb_migrate`, 0 if it has never run.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_get_schema_version(tx: *mut c_void, out_version: *mut u32) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    match schema::version(&tx) {
+        Ok(version) => {
+            unsafe {
+                assert!(!out_version.is_null());
+                *out_version = version;
+            }
+            REDB_OK
+        }
+        Err(err) => error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_get_table_schema(
+    tx: *mut c_void,
+    name: *const c_char,
+    out: *mut redb_table_schema,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let c_str = unsafe {
+        assert!(!name.is_null());
+        std::ffi::CStr::from_ptr(name)
+    };
+    let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
+
+    write_table_schema(schema::get(&tx, str_slice), out)
+}
+
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_delete_table(tx: *mut c_void, name: *const c_char) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+pub extern "C" fn redb_read_tx_validate_table_schema(
+    tx: *mut c_void,
+    name: *const c_char,
+    expected: *const redb_table_schema,
+) -> i32 {
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
 
     let c_str = unsafe {
@@ -381,506 +2592,906 @@ pub extern "C" fn redb_write_tx_delete_table(tx: *mut c_void, name: *const c_cha
         std::ffi::CStr::from_ptr(name)
     };
     let str_slice = c_str.to_str().unwrap();
+    if schema::is_reserved(str_slice) {
+        return REDB_ERROR_RESERVED_TABLE_NAME;
+    }
 
-    match tx.delete_table(TableDefinition::<&[u8], &[u8]>::new(str_slice)) {
-        Ok(_) => REDB_OK,
-        Err(err) => table_error_code(&err),
+    validate_table_schema(schema::get(&tx, str_slice), expected)
+}
+
+/// Returns `REDB_ERROR_INVALID_ENCODING` if `path` or a table name is not valid UTF-8.
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_read_tx_export(
+    tx: *mut c_void,
+    path: *const c_char,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_c_str(path) } {
+        Ok(path) => read_tx_export(tx, &path, table_names, table_count, out_stats),
+        Err(code) => code,
     }
 }
 
+/// Like `redb_read_tx_export`, with the path given as `path_len` UTF-16 code units.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_rename_table(
+pub extern "C" fn redb_read_tx_export_utf16(
     tx: *mut c_void,
-    old_name: *const c_char,
-    new_name: *const c_char,
+    path: *const u16,
+    path_len: usize,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
+) -> i32 {
+    match unsafe { path::from_utf16_raw(path, path_len) } {
+        Ok(path) => read_tx_export(tx, &path, table_names, table_count, out_stats),
+        Err(code) => code,
+    }
+}
+
+fn read_tx_export(
+    tx: *mut c_void,
+    path: &Path,
+    table_names: *const *const c_char,
+    table_count: usize,
+    out_stats: *mut redb_copy_stats,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
+    let Some(tx) = handle::get::<RedbReadTransaction>(tx) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+    let filter = match table_name_filter(table_names, table_count) {
+        Ok(filter) => filter,
+        Err(code) => return code,
     };
 
-    let old_c_str = unsafe {
-        assert!(!old_name.is_null());
-        std::ffi::CStr::from_ptr(old_name)
+    let file = match std::fs::File::create(path) {
+        Ok(file) => file,
+        Err(err) => return file_error_code(&err),
     };
-    let old_str_slice = old_c_str.to_str().unwrap();
 
-    let new_c_str = unsafe {
-        assert!(!new_name.is_null());
-        std::ffi::CStr::from_ptr(new_name)
+    match dump::export(&tx, &mut std::io::BufWriter::new(file), filter) {
+        Ok(stats) => {
+            if !out_stats.is_null() {
+                unsafe {
+                    *out_stats = redb_copy_stats {
+                        tables: stats.tables,
+                        entries: stats.entries,
+                    };
+                }
+            }
+            REDB_OK
+        }
+        Err(err) => dump_error_code(&err),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_get(
+    table: *mut c_void,
+    key: *const u8,
+    key_len: usize,
+    out: *mut redb_blob,
+) -> i32 {
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
-    let new_str_slice = new_c_str.to_str().unwrap();
 
-    match tx.rename_table(
-        TableDefinition::<&[u8], &[u8]>::new(old_str_slice),
-        TableDefinition::<&[u8], &[u8]>::new(new_str_slice),
-    ) {
-        Ok(_) => REDB_OK,
-        Err(err) => table_error_code(&err),
+    let key_slice = unsafe {
+        assert!(!key.is_null());
+        std::slice::from_raw_parts(key, key_len)
+    };
+
+    let emit = |value: &[u8]| {
+        let value_slice = match &table.codec {
+            Some(codec) => match codec.decode(value) {
+                Ok(value) => value,
+                Err(_) => return REDB_ERROR_CODEC,
+            },
+            None => value.into(),
+        };
+        // write to buffer
+        unsafe {
+            *out = redb_blob::new(value_slice.into_owned());
+        }
+        REDB_OK
+    };
+
+    let result = match &table.table {
+        ReadTable::Bytes(table) => table
+            .get(&key_slice)
+            .map(|value| value.map(|value| emit(value.value()))),
+        ReadTable::Typed(table, schema) => {
+            if !schema.key.validate(key_slice) {
+                return REDB_ERROR_INVALID_ENCODING;
+            }
+            typed::with_schema(schema, || {
+                table
+                    .get(&key_slice)
+                    .map(|value| value.map(|value| emit(value.value())))
+            })
+        }
+    };
+
+    match result {
+        Ok(Some(code)) => code,
+        Ok(None) => REDB_ERROR_KEY_NOT_FOUND,
+        Err(_) => REDB_ERROR_STORAGE_ERROR,
     }
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_abort(tx: *mut c_void) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        Box::from_raw(tx as *mut redb::WriteTransaction)
+pub extern "C" fn redb_table_len(table: *mut c_void, out: *mut u64) -> i32 {
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
 
-    match tx.abort() {
-        Ok(_) => REDB_OK,
+    let len = match &table.table {
+        ReadTable::Bytes(table) => table.len(),
+        ReadTable::Typed(table, schema) => typed::with_schema(schema, || table.len()),
+    };
+
+    match len {
+        Ok(len) => {
+            unsafe {
+                *out = len;
+            }
+            REDB_OK
+        }
         Err(_) => REDB_ERROR_STORAGE_ERROR,
     }
 }
 
+// Every handle holds its parent, so the host may free handles in any order: a database stays open
+// until its last transaction is freed, and a write transaction until its last table is.
+struct RedbDatabase {
+    // locked only to start a transaction or to compact, which needs the only reference
+    db: Mutex<Arc<redb::Database>>,
+    // how the database file was opened; `None` for databases without a file
+    file: Option<FileOptions>,
+    // rejects write transactions before they reach the backend, where a failed write would
+    // poison the database for readers too
+    read_only: bool,
+}
+
+/// The options a database file was opened with, kept so `redb_vacuum_into` writes its copy in the
+/// same format and `redb_vacuum_swap` reopens the swapped-in file the same way.
+#[derive(Clone, Default)]
+struct FileOptions {
+    cache_size: Option<usize>,
+    format: FileFormat,
+    mmap: bool,
+    read_only: bool,
+    lock_timeout: Duration,
+}
+
+#[derive(Clone, Default)]
+enum FileFormat {
+    #[default]
+    Plain,
+    Compressed,
+    Encrypted([u8; backends::ENCRYPTION_KEY_SIZE]),
+}
+
+impl FileOptions {
+    fn new(options: Option<&redb_database_options>) -> FileOptions {
+        let Some(opts) = options else {
+            return FileOptions::default();
+        };
+
+        let format = match opts.backend {
+            redb_backend::CompressedFile => FileFormat::Compressed,
+            redb_backend::EncryptedFile => FileFormat::Encrypted(unsafe {
+                assert!(!opts.encryption_key.is_null());
+                *opts
+                    .encryption_key
+                    .cast::<[u8; backends::ENCRYPTION_KEY_SIZE]>()
+            }),
+            _ => FileFormat::Plain,
+        };
+        let mmap = matches!(opts.backend, redb_backend::Mmap);
+
+        FileOptions {
+            cache_size: Some(opts.cache_size),
+            format,
+            mmap,
+            read_only: opts.read_only || mmap,
+            lock_timeout: Duration::from_millis(opts.lock_timeout_ms.into()),
+        }
+    }
+}
+
+impl RedbDatabase {
+    fn new(db: redb::Database, file: Option<FileOptions>, read_only: bool) -> RedbDatabase {
+        RedbDatabase {
+            db: Mutex::new(Arc::new(db)),
+            file,
+            read_only,
+        }
+    }
+
+    fn database(&self) -> Arc<redb::Database> {
+        self.db.lock().unwrap().clone()
+    }
+}
+
+struct RedbWriteTransaction {
+    tx: redb::WriteTransaction,
+    _db: Arc<redb::Database>,
+}
+
+impl std::ops::Deref for RedbWriteTransaction {
+    type Target = redb::WriteTransaction;
+
+    fn deref(&self) -> &redb::WriteTransaction {
+        &self.tx
+    }
+}
+
+impl std::ops::DerefMut for RedbWriteTransaction {
+    fn deref_mut(&mut self) -> &mut redb::WriteTransaction {
+        &mut self.tx
+    }
+}
+
+struct RedbReadTransaction {
+    tx: redb::ReadTransaction,
+    _db: Arc<redb::Database>,
+}
+
+impl std::ops::Deref for RedbReadTransaction {
+    type Target = redb::ReadTransaction;
+
+    fn deref(&self) -> &redb::ReadTransaction {
+        &self.tx
+    }
+}
+
+struct RedbTable {
+    // borrows `tx`, so it is declared first to be dropped first
+    table: Mutex<WriteTable>,
+    codec: Option<Arc<codec::ValueCodec>>,
+    tx: Arc<RedbWriteTransaction>,
+}
+
+enum WriteTable {
+    Bytes(redb::Table<'static, &'static [u8], &'static [u8]>),
+    Typed(
+        redb::Table<'static, typed::TypedKey, typed::TypedValue>,
+        Arc<typed::TypedSchema>,
+    ),
+}
+
+struct RedbReadOnlyTable {
+    table: ReadTable,
+    codec: Option<Arc<codec::ValueCodec>>,
+    tx: Arc<RedbReadTransaction>,
+}
+
+enum ReadTable {
+    Bytes(redb::ReadOnlyTable<&'static [u8], &'static [u8]>),
+    Typed(
+        redb::ReadOnlyTable<typed::TypedKey, typed::TypedValue>,
+        Arc<typed::TypedSchema>,
+    ),
+}
+
+type RangeIter<'a> = Box<
+    dyn Iterator<
+            Item = Result<
+                (
+                    redb::AccessGuard<'a, &'static [u8]>,
+                    redb::AccessGuard<'a, &'static [u8]>,
+                ),
+                redb::StorageError,
+            >,
+        > + 'a,
+>;
+
+struct TypedRangeIter {
+    iter: redb::Range<'static, typed::TypedKey, typed::TypedValue>,
+    schema: Arc<typed::TypedSchema>,
+}
+
+pub struct RedbIterator {
+    inner: Mutex<*mut c_void>,
+    next_fn: unsafe extern "C" fn(*mut c_void, *mut redb_blob, *mut redb_blob) -> i32,
+    free_fn: unsafe fn(*mut c_void),
+    codec: Option<Arc<codec::ValueCodec>>,
+    // `inner` is freed by `free_fn` before this is dropped
+    _tx: Arc<RedbReadTransaction>,
+}
+
+// the iterator state behind `inner` is only touched with its lock held
+unsafe impl Send for RedbIterator {}
+unsafe impl Sync for RedbIterator {}
+
+impl Drop for RedbIterator {
+    fn drop(&mut self) {
+        let inner = *self.inner.get_mut().unwrap();
+        if !inner.is_null() {
+            unsafe { (self.free_fn)(inner) };
+        }
+    }
+}
+
+unsafe fn write_entry(
+    key_slice: &[u8],
+    value_slice: &[u8],
+    key: *mut redb_blob,
+    value: *mut redb_blob,
+) {
+    unsafe {
+        *key = redb_blob::new(key_slice.to_vec());
+        *value = redb_blob::new(value_slice.to_vec());
+    }
+}
+
+unsafe extern "C" fn redb_iter_next_impl(
+    iter_ptr: *mut c_void,
+    key_blob: *mut redb_blob,
+    value_blob: *mut redb_blob,
+) -> i32 {
+    unsafe {
+        let iter = &mut *(iter_ptr as *mut RangeIter);
+
+        match iter.next() {
+            Some(Ok((key, value))) => {
+                write_entry(key.value(), value.value(), key_blob, value_blob);
+                REDB_OK
+            }
+            Some(Err(_)) => REDB_ERROR_STORAGE_ERROR,
+            None => REDB_ERROR_KEY_NOT_FOUND, // End of iterator
+        }
+    }
+}
+
+unsafe extern "C" fn redb_typed_iter_next_impl(
+    iter_ptr: *mut c_void,
+    key_blob: *mut redb_blob,
+    value_blob: *mut redb_blob,
+) -> i32 {
+    unsafe {
+        let TypedRangeIter { iter, schema } = &mut *(iter_ptr as *mut TypedRangeIter);
+
+        typed::with_schema(schema, || match iter.next() {
+            Some(Ok((key, value))) => {
+                write_entry(key.value(), value.value(), key_blob, value_blob);
+                REDB_OK
+            }
+            Some(Err(_)) => REDB_ERROR_STORAGE_ERROR,
+            None => REDB_ERROR_KEY_NOT_FOUND, // End of iterator
+        })
+    }
+}
+
+unsafe fn free_range_iter(iter: *mut c_void) {
+    unsafe { drop(Box::from_raw(iter as *mut RangeIter)) }
+}
+
+unsafe fn free_typed_range_iter(iter: *mut c_void) {
+    unsafe { drop(Box::from_raw(iter as *mut TypedRangeIter)) }
+}
+
+fn table_range(
+    table: &RedbReadOnlyTable,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    out: *mut *mut c_void,
+) -> i32 {
+    let (inner, next_fn, free_fn): (_, _, unsafe fn(*mut c_void)) = match &table.table {
+        ReadTable::Bytes(table) => match table.range::<&[u8]>((start, end)) {
+            Ok(iter) => {
+                let boxed_iter: RangeIter = Box::new(iter);
+                (
+                    Box::into_raw(Box::new(boxed_iter)) as *mut c_void,
+                    redb_iter_next_impl as unsafe extern "C" fn(_, _, _) -> _,
+                    free_range_iter,
+                )
+            }
+            Err(_) => return REDB_ERROR_STORAGE_ERROR,
+        },
+        ReadTable::Typed(table, schema) => {
+            let valid = |bound: Bound<&[u8]>| match bound {
+                Bound::Included(key) | Bound::Excluded(key) => schema.key.validate(key),
+                Bound::Unbounded => true,
+            };
+            if !valid(start) || !valid(end) {
+                return REDB_ERROR_INVALID_ENCODING;
+            }
+
+            match typed::with_schema(schema, || table.range::<&[u8]>((start, end))) {
+                Ok(iter) => (
+                    Box::into_raw(Box::new(TypedRangeIter {
+                        iter,
+                        schema: schema.clone(),
+                    })) as *mut c_void,
+                    redb_typed_iter_next_impl,
+                    free_typed_range_iter,
+                ),
+                Err(_) => return REDB_ERROR_STORAGE_ERROR,
+            }
+        }
+    };
+
+    unsafe {
+        *out = handle::insert(RedbIterator {
+            inner: Mutex::new(inner),
+            next_fn,
+            free_fn,
+            codec: table.codec.clone(),
+            _tx: table.tx.clone(),
+        });
+    }
+    REDB_OK
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_iter(table: *mut c_void, out: *mut *mut c_void) -> i32 {
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    table_range(&table, Bound::Unbounded, Bound::Unbounded, out)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_range(
+    table: *mut c_void,
+    start_key: *const u8,
+    start_key_len: usize,
+    end_key: *const u8,
+    end_key_len: usize,
+    out: *mut *mut c_void,
+) -> i32 {
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
+    };
+
+    let start = if !start_key.is_null() && start_key_len > 0 {
+        Bound::Included(unsafe { std::slice::from_raw_parts(start_key, start_key_len) })
+    } else {
+        Bound::Unbounded
+    };
+
+    let end = if !end_key.is_null() && end_key_len > 0 {
+        Bound::Excluded(unsafe { std::slice::from_raw_parts(end_key, end_key_len) })
+    } else {
+        Bound::Unbounded
+    };
+
+    table_range(&table, start, end, out)
+}
+
+// The numeric exports accept any typed table whose key is the matching integer type, and return
+// `REDB_ERROR_TABLE_TYPE_MISMATCH` for every other table. Range bounds may be null for an
+// unbounded end; the end bound is exclusive, as in `redb_range`.
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_insert_u64(
+    table: *mut c_void,
+    key: u64,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    insert_numeric(
+        table,
+        redb_numeric_key::U64,
+        &key.to_le_bytes(),
+        value,
+        value_len,
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_insert_i64(
+    table: *mut c_void,
+    key: i64,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    insert_numeric(
+        table,
+        redb_numeric_key::I64,
+        &key.to_le_bytes(),
+        value,
+        value_len,
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_insert_u32(
+    table: *mut c_void,
+    key: u32,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    insert_numeric(
+        table,
+        redb_numeric_key::U32,
+        &key.to_le_bytes(),
+        value,
+        value_len,
+    )
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_insert_i32(
+    table: *mut c_void,
+    key: i32,
+    value: *const u8,
+    value_len: usize,
+) -> i32 {
+    insert_numeric(
+        table,
+        redb_numeric_key::I32,
+        &key.to_le_bytes(),
+        value,
+        value_len,
+    )
+}
+
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_commit(tx: *mut c_void) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        Box::from_raw(tx as *mut redb::WriteTransaction)
-    };
-
-    match tx.commit() {
-        Ok(_) => REDB_OK,
-        Err(err) => match err {
-            redb::CommitError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
-            _ => todo!(),
-        },
-    }
+pub extern "C" fn redb_get_u64(table: *mut c_void, key: u64, out: *mut redb_blob) -> i32 {
+    get_numeric(table, redb_numeric_key::U64, &key.to_le_bytes(), out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_ephemeral_savepoint(tx: *mut c_void, out: *mut *mut c_void) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    match tx.ephemeral_savepoint() {
-        Ok(savepoint) => {
-            unsafe {
-                *out = Box::into_raw(Box::new(savepoint)) as *mut c_void;
-            };
-            REDB_OK
-        }
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
-    }
+pub extern "C" fn redb_get_i64(table: *mut c_void, key: i64, out: *mut redb_blob) -> i32 {
+    get_numeric(table, redb_numeric_key::I64, &key.to_le_bytes(), out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_restore_savepoint(tx: *mut c_void, savepoint: *mut c_void) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    let savepoint = unsafe {
-        assert!(!savepoint.is_null());
-        &*(savepoint as *mut redb::Savepoint)
-    };
-
-    match tx.restore_savepoint(savepoint) {
-        Ok(_) => REDB_OK,
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
-    }
+pub extern "C" fn redb_get_u32(table: *mut c_void, key: u32, out: *mut redb_blob) -> i32 {
+    get_numeric(table, redb_numeric_key::U32, &key.to_le_bytes(), out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_persistent_savepoint(tx: *mut c_void, out: *mut u64) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    match tx.persistent_savepoint() {
-        Ok(id) => {
-            unsafe {
-                *out = id;
-            };
-            REDB_OK
-        }
-        Err(err) => match err {
-            redb::SavepointError::InvalidSavepoint => REDB_ERROR_INVALID_SAVEPOINT,
-            redb::SavepointError::Storage(_) => REDB_ERROR_STORAGE_ERROR,
-            _ => todo!(),
-        },
-    }
+pub extern "C" fn redb_get_i32(table: *mut c_void, key: i32, out: *mut redb_blob) -> i32 {
+    get_numeric(table, redb_numeric_key::I32, &key.to_le_bytes(), out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_get_persistent_savepoint(
-    tx: *mut c_void,
-    id: u64,
+pub extern "C" fn redb_range_u64(
+    table: *mut c_void,
+    start: *const u64,
+    end: *const u64,
     out: *mut *mut c_void,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    match tx.get_persistent_savepoint(id) {
-        Ok(savepoint) => {
-            unsafe {
-                *out = Box::into_raw(Box::new(savepoint)) as *mut c_void;
-            };
-            REDB_OK
-        }
-        Err(err) => savepoint_error_code(&err),
-    }
+    let start = unsafe { start.as_ref() }.map(|key| key.to_le_bytes());
+    let end = unsafe { end.as_ref() }.map(|key| key.to_le_bytes());
+    range_numeric(table, redb_numeric_key::U64, start, end, out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_delete_persistent_savepoint(
-    tx: *mut c_void,
-    id: u64,
-    out: *mut bool,
+pub extern "C" fn redb_range_i64(
+    table: *mut c_void,
+    start: *const i64,
+    end: *const i64,
+    out: *mut *mut c_void,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    match tx.delete_persistent_savepoint(id) {
-        Ok(ret) => {
-            unsafe {
-                *out = ret;
-            };
-            REDB_OK
-        }
-        Err(err) => savepoint_error_code(&err),
-    }
+    let start = unsafe { start.as_ref() }.map(|key| key.to_le_bytes());
+    let end = unsafe { end.as_ref() }.map(|key| key.to_le_bytes());
+    range_numeric(table, redb_numeric_key::I64, start, end, out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_write_tx_lists_persistent_savepoint(
-    tx: *mut c_void,
-    out: *mut *mut u64,
-    count: *mut usize,
+pub extern "C" fn redb_range_u32(
+    table: *mut c_void,
+    start: *const u32,
+    end: *const u32,
+    out: *mut *mut c_void,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *(tx as *mut redb::WriteTransaction)
-    };
-
-    let iter = match tx.list_persistent_savepoints() {
-        Ok(iter) => iter,
-        Err(_) => return REDB_ERROR_STORAGE_ERROR,
-    };
-
-    let result = iter.collect::<Vec<_>>();
-    unsafe {
-        *count = result.len();
-        *out = Box::into_raw(result.into_boxed_slice()) as *mut u64;
-    }
-
-    REDB_OK
+    let start = unsafe { start.as_ref() }.map(|key| key.to_le_bytes());
+    let end = unsafe { end.as_ref() }.map(|key| key.to_le_bytes());
+    range_numeric(table, redb_numeric_key::U32, start, end, out)
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_insert(
+pub extern "C" fn redb_range_i32(
     table: *mut c_void,
-    key: *const u8,
-    key_len: usize,
+    start: *const i32,
+    end: *const i32,
+    out: *mut *mut c_void,
+) -> i32 {
+    let start = unsafe { start.as_ref() }.map(|key| key.to_le_bytes());
+    let end = unsafe { end.as_ref() }.map(|key| key.to_le_bytes());
+    range_numeric(table, redb_numeric_key::I32, start, end, out)
+}
+
+fn insert_numeric(
+    table: *mut c_void,
+    key_type: redb_numeric_key,
+    key: &[u8],
     value: *const u8,
     value_len: usize,
 ) -> i32 {
-    let table = unsafe {
-        assert!(!table.is_null());
-        &mut *(table as *mut redb::Table<&[u8], &[u8]>)
-    };
-
-    let key_slice = unsafe {
-        assert!(!key.is_null());
-        std::slice::from_raw_parts(key, key_len)
-    };
-
-    let value_slice = unsafe {
-        assert!(!value.is_null());
-        std::slice::from_raw_parts(value, value_len)
+    let key_type = numeric_key_type(key_type);
+    let Some(table_handle) = handle::get::<RedbTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
-
-    match table.insert(key_slice, value_slice) {
-        Ok(_) => REDB_OK,
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
+    let matches = matches!(
+        &*table_handle.table.lock().unwrap(),
+        WriteTable::Typed(_, schema) if schema.key == key_type
+    );
+    if !matches {
+        return REDB_ERROR_TABLE_TYPE_MISMATCH;
     }
-}
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_begin_read(db: *mut c_void, out: *mut *mut c_void) -> i32 {
-    let db = unsafe {
-        assert!(!db.is_null());
-        &*db.cast::<redb::Database>()
-    };
+    redb_insert(table, key.as_ptr(), key.len(), value, value_len)
+}
 
-    match db.begin_read() {
-        Ok(tx) => {
-            unsafe {
-                *out = Box::into_raw(Box::new(tx)) as *mut c_void;
-            };
+fn check_readonly_key(table: *mut c_void, key_type: redb_numeric_key) -> i32 {
+    let key_type = numeric_key_type(key_type);
+    match handle::get::<RedbReadOnlyTable>(table) {
+        Some(table) if matches!(&table.table, ReadTable::Typed(_, schema) if schema.key == key_type) => {
             REDB_OK
         }
-        Err(err) => transaction_error_code(&err),
+        Some(_) => REDB_ERROR_TABLE_TYPE_MISMATCH,
+        None => REDB_ERROR_INVALID_HANDLE,
     }
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_read_tx_open_table(
-    tx: *mut c_void,
-    name: *const c_char,
+fn get_numeric(
+    table: *mut c_void,
+    key_type: redb_numeric_key,
+    key: &[u8],
+    out: *mut redb_blob,
+) -> i32 {
+    let code = check_readonly_key(table, key_type);
+    if code != REDB_OK {
+        return code;
+    }
+
+    redb_get(table, key.as_ptr(), key.len(), out)
+}
+
+fn range_numeric<const N: usize>(
+    table: *mut c_void,
+    key_type: redb_numeric_key,
+    start: Option<[u8; N]>,
+    end: Option<[u8; N]>,
     out: *mut *mut c_void,
 ) -> i32 {
-    let tx = unsafe {
-        assert!(!tx.is_null());
-        &mut *tx.cast::<redb::ReadTransaction>()
-    };
+    let code = check_readonly_key(table, key_type);
+    if code != REDB_OK {
+        return code;
+    }
 
-    let c_str = unsafe {
-        assert!(!name.is_null());
-        std::ffi::CStr::from_ptr(name)
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
-    let str_slice = c_str.to_str().unwrap();
+    let start = start
+        .as_ref()
+        .map_or(Bound::Unbounded, |key| Bound::Included(key.as_slice()));
+    let end = end
+        .as_ref()
+        .map_or(Bound::Unbounded, |key| Bound::Excluded(key.as_slice()));
+
+    table_range(&table, start, end, out)
+}
 
-    match tx.open_table(TableDefinition::<&[u8], &[u8]>::new(str_slice)) {
-        Ok(table) => {
-            unsafe {
-                *out = Box::into_raw(Box::new(table)) as *mut c_void;
-            };
-            REDB_OK
-        }
-        Err(err) => table_error_code(&err),
+unsafe fn key_parts<'a>(parts: *const redb_key_part, part_count: usize) -> Vec<&'a [u8]> {
+    if part_count == 0 {
+        return Vec::new();
+    }
+    unsafe {
+        assert!(!parts.is_null());
+        std::slice::from_raw_parts(parts, part_count)
+            .iter()
+            .map(|part| {
+                if part.len == 0 {
+                    &[][..]
+                } else {
+                    std::slice::from_raw_parts(part.data, part.len)
+                }
+            })
+            .collect()
     }
 }
 
+/// Encodes a composite key from the redb encodings of its `component_count` components, for use
+/// with `redb_insert`, `redb_get` and `redb_range` on a composite table. Free it with
+/// `redb_free_blob`.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_get(
-    table: *mut c_void,
-    key: *const u8,
-    key_len: usize,
-    blob: *mut *mut u8,
-    count: *mut usize,
+pub extern "C" fn redb_encode_composite_key(
+    components: *const redb_type_descriptor,
+    component_count: usize,
+    parts: *const redb_key_part,
+    out: *mut redb_blob,
 ) -> i32 {
-    let table = unsafe {
-        assert!(!table.is_null());
-        &*table.cast::<redb::ReadOnlyTable<&[u8], &[u8]>>()
-    };
-
-    let key_slice = unsafe {
-        assert!(!key.is_null());
-        std::slice::from_raw_parts(key as *const u8, key_len)
+    let elements = match composite_elements(components, component_count) {
+        Ok(elements) => elements,
+        Err(code) => return code,
     };
+    let parts = unsafe { key_parts(parts, component_count) };
 
-    match table.get(&key_slice) {
-        Ok(Some(value)) => {
-            let value_slice = value.value();
-            let value_len = value_slice.len();
-            // write to buffer
+    match typed::encode_composite(&elements, &parts) {
+        Some(key) => {
             unsafe {
-                *blob = Box::into_raw(value_slice.to_vec().into_boxed_slice()) as *mut u8;
-                *count = value_len;
+                *out = redb_blob::new(key);
             }
             REDB_OK
         }
-        Ok(None) => REDB_ERROR_KEY_NOT_FOUND,
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
+        None => REDB_ERROR_INVALID_ENCODING,
     }
 }
 
+/// Splits a composite key into its `component_count` components. The parts written to
+/// `out_parts` point into `key` and are only valid as long as it is.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_table_len(table: *mut c_void, out: *mut u64) -> i32 {
-    let table = unsafe {
-        assert!(!table.is_null());
-        &*table.cast::<redb::ReadOnlyTable<&[u8], &[u8]>>()
+pub extern "C" fn redb_decode_composite_key(
+    components: *const redb_type_descriptor,
+    component_count: usize,
+    key: *const u8,
+    key_len: usize,
+    out_parts: *mut redb_key_part,
+) -> i32 {
+    let elements = match composite_elements(components, component_count) {
+        Ok(elements) => elements,
+        Err(code) => return code,
+    };
+    let key = if key_len == 0 {
+        &[][..]
+    } else {
+        unsafe {
+            assert!(!key.is_null());
+            std::slice::from_raw_parts(key, key_len)
+        }
     };
 
-    match table.len() {
-        Ok(len) => {
-            unsafe {
-                *out = len;
-            }
-            REDB_OK
+    let Some(parts) = typed::decode_composite(&elements, key) else {
+        return REDB_ERROR_INVALID_ENCODING;
+    };
+    unsafe {
+        assert!(!out_parts.is_null());
+        for (i, part) in parts.into_iter().enumerate() {
+            *out_parts.add(i) = redb_key_part {
+                data: part.as_ptr(),
+                len: part.len(),
+            };
         }
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
     }
+    REDB_OK
 }
 
-type RangeIter<'a> = Box<
-    dyn Iterator<
-            Item = Result<
-                (
-                    redb::AccessGuard<'a, &'static [u8]>,
-                    redb::AccessGuard<'a, &'static [u8]>,
-                ),
-                redb::StorageError,
-            >,
-        > + 'a,
->;
-
-pub struct RedbIterator {
-    inner: *mut c_void,
-    next_fn: unsafe extern "C" fn(
-        *mut c_void,
-        *mut *mut u8,
-        *mut usize,
-        *mut *mut u8,
-        *mut usize,
-    ) -> i32,
+struct PrefixRangeIter {
+    range: TypedRangeIter,
+    prefix: Vec<Vec<u8>>,
 }
 
-unsafe impl Send for RedbIterator {}
-
-unsafe extern "C" fn redb_iter_next_impl(
+unsafe extern "C" fn redb_prefix_iter_next_impl(
     iter_ptr: *mut c_void,
-    key_blob: *mut *mut u8,
-    key_len: *mut usize,
-    value_blob: *mut *mut u8,
-    value_len: *mut usize,
-) -> i32 {
-    type IterType<'a> = Box<
-        dyn Iterator<
-                Item = Result<
-                    (
-                        redb::AccessGuard<'a, &'static [u8]>,
-                        redb::AccessGuard<'a, &'static [u8]>,
-                    ),
-                    redb::StorageError,
-                >,
-            > + 'a,
-    >;
-
+    key_blob: *mut redb_blob,
+    value_blob: *mut redb_blob,
+) -> i32 {
     unsafe {
-        let iter = &mut *(iter_ptr as *mut IterType);
-
-        match iter.next() {
-            Some(Ok((key, value))) => {
-                let key_slice = key.value();
-                let value_slice = value.value();
-
-                *key_blob = Box::into_raw(key_slice.to_vec().into_boxed_slice()) as *mut u8;
-                *key_len = key_slice.len();
-                *value_blob = Box::into_raw(value_slice.to_vec().into_boxed_slice()) as *mut u8;
-                *value_len = value_slice.len();
-
-                REDB_OK
+        let PrefixRangeIter { range, prefix } = &mut *(iter_ptr as *mut PrefixRangeIter);
+        let typed::DynType::Tuple(elements) = &range.schema.key else {
+            unreachable!("prefix scans are only started on composite tables");
+        };
+        let prefix: Vec<_> = prefix.iter().map(Vec::as_slice).collect();
+
+        typed::with_schema(&range.schema, || {
+            loop {
+                match range.iter.next() {
+                    Some(Ok((key, value))) => {
+                        match typed::compare_prefix(elements, key.value(), &prefix) {
+                            // only reached when the prefix has no smallest key to start from
+                            std::cmp::Ordering::Less => continue,
+                            std::cmp::Ordering::Equal => {
+                                write_entry(key.value(), value.value(), key_blob, value_blob);
+                                return REDB_OK;
+                            }
+                            std::cmp::Ordering::Greater => return REDB_ERROR_KEY_NOT_FOUND,
+                        }
+                    }
+                    Some(Err(_)) => return REDB_ERROR_STORAGE_ERROR,
+                    None => return REDB_ERROR_KEY_NOT_FOUND, // End of iterator
+                }
             }
-            Some(Err(_)) => REDB_ERROR_STORAGE_ERROR,
-            None => REDB_ERROR_KEY_NOT_FOUND, // End of iterator
-        }
+        })
     }
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_iter(table: *mut c_void, out: *mut *mut c_void) -> i32 {
-    let table = unsafe {
-        assert!(!table.is_null());
-        &*table.cast::<redb::ReadOnlyTable<&'static [u8], &'static [u8]>>()
-    };
-
-    match table.iter() {
-        Ok(iter) => {
-            let boxed_iter: RangeIter = Box::new(iter);
-            let iter_handle = RedbIterator {
-                inner: Box::into_raw(Box::new(boxed_iter)) as *mut c_void,
-                next_fn: redb_iter_next_impl,
-            };
-
-            unsafe {
-                *out = Box::into_raw(Box::new(iter_handle)) as *mut c_void;
-            }
-            REDB_OK
-        }
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
-    }
+unsafe fn free_prefix_range_iter(iter: *mut c_void) {
+    unsafe { drop(Box::from_raw(iter as *mut PrefixRangeIter)) }
 }
 
+/// Iterates the entries of a composite table whose keys start with the `part_count` leading
+/// components in `parts`, in key order. Zero parts iterates the whole table.
 #[unsafe(no_mangle)]
-pub extern "C" fn redb_range(
+pub extern "C" fn redb_range_prefix(
     table: *mut c_void,
-    start_key: *const u8,
-    start_key_len: usize,
-    end_key: *const u8,
-    end_key_len: usize,
+    parts: *const redb_key_part,
+    part_count: usize,
     out: *mut *mut c_void,
 ) -> i32 {
-    let table = unsafe {
-        assert!(!table.is_null());
-        &*table.cast::<redb::ReadOnlyTable<&'static [u8], &'static [u8]>>()
+    let Some(table) = handle::get::<RedbReadOnlyTable>(table) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
 
-    let start = if !start_key.is_null() && start_key_len > 0 {
-        let slice = unsafe { std::slice::from_raw_parts(start_key, start_key_len) };
-        Some(slice.to_vec())
-    } else {
-        None
+    let ReadTable::Typed(inner, schema) = &table.table else {
+        return REDB_ERROR_TABLE_TYPE_MISMATCH;
     };
-
-    let end = if !end_key.is_null() && end_key_len > 0 {
-        let slice = unsafe { std::slice::from_raw_parts(end_key, end_key_len) };
-        Some(slice.to_vec())
-    } else {
-        None
+    let typed::DynType::Tuple(elements) = &schema.key else {
+        return REDB_ERROR_TABLE_TYPE_MISMATCH;
     };
+    if part_count > elements.len() {
+        return REDB_ERROR_INVALID_ENCODING;
+    }
+    let prefix = unsafe { key_parts(parts, part_count) };
+    if !elements
+        .iter()
+        .zip(&prefix)
+        .all(|(element, part)| element.validate(part))
+    {
+        return REDB_ERROR_INVALID_ENCODING;
+    }
 
-    let range_iter = match (&start, &end) {
-        (Some(s), Some(e)) => {
-            let s_slice: &[u8] = s.as_slice();
-            let e_slice: &[u8] = e.as_slice();
-            table.range::<&[u8]>(s_slice..e_slice)
-        }
-        (Some(s), None) => {
-            let s_slice: &[u8] = s.as_slice();
-            table.range::<&[u8]>(s_slice..)
-        }
-        (None, Some(e)) => {
-            let e_slice: &[u8] = e.as_slice();
-            table.range::<&[u8]>(..e_slice)
-        }
-        (None, None) => table.range::<&[u8]>(..),
+    let start = typed::prefix_lower_bound(elements, &prefix);
+    let start = start.as_deref().map_or(Bound::Unbounded, Bound::Included);
+    let iter = match typed::with_schema(schema, || inner.range::<&[u8]>((start, Bound::Unbounded)))
+    {
+        Ok(iter) => iter,
+        Err(_) => return REDB_ERROR_STORAGE_ERROR,
     };
 
-    match range_iter {
-        Ok(iter) => {
-            let boxed_iter: RangeIter = Box::new(iter);
-            let iter_handle = RedbIterator {
-                inner: Box::into_raw(Box::new(boxed_iter)) as *mut c_void,
-                next_fn: redb_iter_next_impl,
-            };
-
-            unsafe {
-                *out = Box::into_raw(Box::new(iter_handle)) as *mut c_void;
-            }
-            REDB_OK
-        }
-        Err(_) => REDB_ERROR_STORAGE_ERROR,
+    unsafe {
+        *out = handle::insert(RedbIterator {
+            inner: Mutex::new(Box::into_raw(Box::new(PrefixRangeIter {
+                range: TypedRangeIter {
+                    iter,
+                    schema: schema.clone(),
+                },
+                prefix: prefix.iter().map(|part| part.to_vec()).collect(),
+            })) as *mut c_void),
+            next_fn: redb_prefix_iter_next_impl,
+            free_fn: free_prefix_range_iter,
+            codec: table.codec.clone(),
+            _tx: table.tx.clone(),
+        });
     }
+    REDB_OK
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn redb_iter_next(
     iter: *mut c_void,
-    key_blob: *mut *mut u8,
-    key_len: *mut usize,
-    value_blob: *mut *mut u8,
-    value_len: *mut usize,
+    key_blob: *mut redb_blob,
+    value_blob: *mut redb_blob,
 ) -> i32 {
-    let iter_handle = unsafe {
-        assert!(!iter.is_null());
-        &mut *iter.cast::<RedbIterator>()
+    let Some(iter_handle) = handle::get::<RedbIterator>(iter) else {
+        return REDB_ERROR_INVALID_HANDLE;
     };
 
-    unsafe { (iter_handle.next_fn)(iter_handle.inner, key_blob, key_len, value_blob, value_len) }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn redb_free_iter(iter: *mut c_void) {
-    if iter.is_null() {
-        return;
+    let code = {
+        let inner = iter_handle.inner.lock().unwrap();
+        unsafe { (iter_handle.next_fn)(*inner, key_blob, value_blob) }
+    };
+    let Some(codec) = &iter_handle.codec else {
+        return code;
+    };
+    if code != REDB_OK {
+        return code;
     }
 
     unsafe {
-        let iter = iter as *mut RedbIterator;
-        if !(*iter).inner.is_null() {
-            drop(Box::from_raw((*iter).inner as *mut RangeIter));
+        let encoded = value_blob.replace(redb_blob::EMPTY).into_vec();
+        match codec.decode(&encoded) {
+            Ok(value) => {
+                *value_blob = redb_blob::new(value.into_owned());
+                REDB_OK
+            }
+            Err(_) => {
+                redb_free_blob(key_blob);
+                REDB_ERROR_CODEC
+            }
         }
-        drop(Box::from_raw(iter));
     }
 }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn redb_free_iter(iter: *mut c_void) {
+    drop(handle::remove::<RedbIterator>(iter));
+}
@@ -0,0 +1,139 @@
+//! Waiting for and probing the advisory locks that keep a database file to a single process.
+
+use std::{
+    fs::{File, TryLockError},
+    io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Calls `open` until it stops failing with `WouldBlock` or `timeout` has passed, sleeping with
+/// exponential backoff in between. A zero timeout tries exactly once.
+pub(crate) fn retry_while_locked<T>(
+    timeout: Duration,
+    mut open: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let deadline = Instant::now() + timeout;
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        match open() {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(err);
+                }
+                thread::sleep(backoff.min(deadline - now));
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns whether any process holds a lock on the file at `path`, whether exclusive (a writer)
+/// or shared (a read-only reader).
+///
+/// The probe takes and releases an exclusive lock, so an open racing with it may briefly see the
+/// file as locked.
+pub(crate) fn is_locked(path: &Path) -> io::Result<bool> {
+    let file = File::open(path)?;
+    match file.try_lock() {
+        Ok(()) => {
+            file.unlock()?;
+            Ok(false)
+        }
+        Err(TryLockError::WouldBlock) => Ok(true),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn open(path: &std::ffi::CStr, read_only: bool) -> (i32, *mut c_void) {
+        open_waiting(path, read_only, 0)
+    }
+
+    fn open_waiting(
+        path: &std::ffi::CStr,
+        read_only: bool,
+        lock_timeout_ms: u32,
+    ) -> (i32, *mut c_void) {
+        let options = redb_database_options {
+            cache_size: 1 << 20,
+            backend: redb_backend::File,
+            storage: ptr::null(),
+            encryption_key: ptr::null(),
+            read_only,
+            lock_timeout_ms,
+        };
+        let mut db = ptr::null_mut();
+        let code = redb_create_database(path.as_ptr(), &options, &mut db);
+        (code, db)
+    }
+
+    #[test]
+    fn read_only_opens_share_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("redb-ffi-shared-{}.redb", std::process::id()));
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+
+        let (code, db) = open(&c_path, false);
+        assert_eq!(code, REDB_OK);
+        redb_free_database(db);
+
+        let (code, first) = open(&c_path, true);
+        assert_eq!(code, REDB_OK);
+        let (code, second) = open(&c_path, true);
+        assert_eq!(code, REDB_OK);
+
+        // readers still keep writers out
+        assert_eq!(open(&c_path, false).0, REDB_ERROR_DATABASE_ALREADY_OPEN);
+        redb_free_database(first);
+        redb_free_database(second);
+        let (code, db) = open(&c_path, false);
+        assert_eq!(code, REDB_OK);
+        redb_free_database(db);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn waits_for_the_lock_holder() {
+        let path =
+            std::env::temp_dir().join(format!("redb-ffi-waiting-{}.redb", std::process::id()));
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+
+        let (code, holder) = open(&c_path, false);
+        assert_eq!(code, REDB_OK);
+
+        let started = std::time::Instant::now();
+        assert_eq!(
+            open_waiting(&c_path, false, 50).0,
+            REDB_ERROR_DATABASE_ALREADY_OPEN
+        );
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // handles are plain pointers, which threads cannot share
+        let started = std::time::Instant::now();
+        let holder = holder as usize;
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            redb_free_database(holder as *mut c_void);
+        });
+        let (code, db) = open_waiting(&c_path, false, 10_000);
+        assert_eq!(code, REDB_OK);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        release.join().unwrap();
+        redb_free_database(db);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! Decoding of database paths passed across the FFI boundary.
+
+use crate::{REDB_ERROR_INVALID_ENCODING, REDB_ERROR_INVALID_PATH};
+use std::{ffi::c_char, path::PathBuf};
+
+/// Decodes a NUL-terminated UTF-8 path.
+///
+/// # Safety
+/// `path` must point to a NUL-terminated string.
+pub(crate) unsafe fn from_c_str(path: *const c_char) -> Result<PathBuf, i32> {
+    assert!(!path.is_null());
+    let c_str = unsafe { std::ffi::CStr::from_ptr(path) };
+    from_utf8(c_str.to_bytes())
+}
+
+/// Decodes a UTF-8 path of `len` bytes.
+///
+/// # Safety
+/// `path` must be valid for reads of `len` bytes.
+pub(crate) unsafe fn from_utf8_raw(path: *const u8, len: usize) -> Result<PathBuf, i32> {
+    from_utf8(unsafe { slice(path, len) })
+}
+
+/// Decodes a UTF-16 path of `len` code units. On Windows unpaired surrogates are kept, as the
+/// file system accepts them; elsewhere they are rejected with `REDB_ERROR_INVALID_ENCODING`.
+///
+/// # Safety
+/// `path` must be valid for reads of `len` code units.
+pub(crate) unsafe fn from_utf16_raw(path: *const u16, len: usize) -> Result<PathBuf, i32> {
+    let units = unsafe { slice(path, len) };
+    if units.is_empty() || units.contains(&0) {
+        return Err(REDB_ERROR_INVALID_PATH);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStringExt;
+        Ok(std::ffi::OsString::from_wide(units).into())
+    }
+    #[cfg(not(windows))]
+    {
+        String::from_utf16(units)
+            .map(PathBuf::from)
+            .map_err(|_| REDB_ERROR_INVALID_ENCODING)
+    }
+}
+
+fn from_utf8(bytes: &[u8]) -> Result<PathBuf, i32> {
+    if bytes.is_empty() || bytes.contains(&0) {
+        return Err(REDB_ERROR_INVALID_PATH);
+    }
+
+    std::str::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|_| REDB_ERROR_INVALID_ENCODING)
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        assert!(!ptr.is_null());
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_non_ascii_paths() {
+        let path = "données/日本語.redb";
+        let units: Vec<u16> = path.encode_utf16().collect();
+        assert_eq!(
+            unsafe { from_utf16_raw(units.as_ptr(), units.len()) },
+            Ok(PathBuf::from(path))
+        );
+        assert_eq!(
+            unsafe { from_utf8_raw(path.as_ptr(), path.len()) },
+            Ok(PathBuf::from(path))
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn rejects_unpaired_surrogates() {
+        let units = [u16::from(b'a'), 0xd800, u16::from(b'b')];
+        assert_eq!(
+            unsafe { from_utf16_raw(units.as_ptr(), units.len()) },
+            Err(REDB_ERROR_INVALID_ENCODING)
+        );
+        assert_eq!(
+            unsafe { from_utf8_raw([b'a', 0xff].as_ptr(), 2) },
+            Err(REDB_ERROR_INVALID_ENCODING)
+        );
+        assert_eq!(
+            unsafe { from_utf16_raw(units.as_ptr(), 0) },
+            Err(REDB_ERROR_INVALID_PATH)
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Byte-level access to tables whose key and value types are only known at runtime.
+//!
+//! redb resolves type names, fixed widths and key ordering through static trait functions, so
+//! `RawKey` and `RawValue` read them from a thread-local schema that is installed for the
+//! duration of a copy. Keys are never compared by their real ordering: entries are always
+//! inserted in the order the source table yields them, so the key being inserted is simply
+//! ordered after every key already present.
+
+use crate::typed::DynType;
+use redb::{Key, TableError, TypeName, Value};
+use std::{cell::RefCell, cmp::Ordering};
+
+#[derive(Clone, Debug)]
+pub(crate) struct RawSchema {
+    pub key_type: TypeName,
+    pub key_width: Option<usize>,
+    pub value_type: TypeName,
+    pub value_width: Option<usize>,
+}
+
+impl RawSchema {
+    fn probe() -> Self {
+        Self {
+            key_type: TypeName::new("redb-ffi::probe"),
+            key_width: None,
+            value_type: TypeName::new("redb-ffi::probe"),
+            value_width: None,
+        }
+    }
+}
+
+thread_local! {
+    static SCHEMA: RefCell<Option<RawSchema>> = const { RefCell::new(None) };
+    static PENDING_KEY: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    static PENDING_VALUE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn with_current<R>(f: impl FnOnce(&RawSchema) -> R) -> R {
+    SCHEMA.with(|schema| {
+        f(schema
+            .borrow()
+            .as_ref()
+            .expect("raw table used without a schema"))
+    })
+}
+
+/// Runs `f` with `schema` installed for `RawKey` and `RawValue`.
+pub(crate) fn with_schema<R>(schema: &RawSchema, f: impl FnOnce() -> R) -> R {
+    let previous = SCHEMA.with(|current| current.replace(Some(schema.clone())));
+    let result = f();
+    SCHEMA.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Discovers the stored schema of a table by opening it with a probe definition and reading the
+/// actual types back out of the mismatch errors.
+pub(crate) fn probe_schema(
+    mut open: impl FnMut() -> Result<(), TableError>,
+) -> Result<RawSchema, TableError> {
+    let mut schema = RawSchema::probe();
+
+    // names, key width and value width are each reported by a separate error
+    for _ in 0..4 {
+        match with_schema(&schema, &mut open) {
+            Ok(()) => return Ok(schema),
+            Err(TableError::TableTypeMismatch { key, value, .. }) => {
+                schema.key_type = key;
+                schema.value_type = value;
+            }
+            Err(TableError::TypeDefinitionChanged { name, width, .. }) => {
+                if schema.key_type == schema.value_type {
+                    schema.key_width = width;
+                    schema.value_width = width;
+                } else if name == schema.key_type {
+                    schema.key_width = width;
+                } else {
+                    schema.value_width = width;
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    with_schema(&schema, open).map(|()| schema)
+}
+
+/// Marks `key` (and `value`, for multimap tables) as the entry about to be inserted.
+pub(crate) fn set_pending(key: &[u8], value: Option<&[u8]>) {
+    PENDING_KEY.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        pending.clear();
+        pending.extend_from_slice(key);
+    });
+    if let Some(value) = value {
+        PENDING_VALUE.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            pending.clear();
+            pending.extend_from_slice(value);
+        });
+    }
+}
+
+/// Checks that the entries of a table arrive in the order the table sorts them, which appending
+/// them relies on. Keys and values whose ordering this build cannot reproduce are only checked
+/// for repeats.
+pub(crate) struct EntryOrder {
+    key: Option<DynType>,
+    // multimap values are ordered within their key
+    value: Option<Option<DynType>>,
+    previous: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl EntryOrder {
+    pub(crate) fn new(schema: &RawSchema, multimap: bool) -> Self {
+        Self {
+            key: ordering(&schema.key_type),
+            value: multimap.then(|| ordering(&schema.value_type)),
+            previous: None,
+        }
+    }
+
+    /// Whether the entry is valid and sorts after the previous one.
+    pub(crate) fn follows(&mut self, key: &[u8], value: &[u8]) -> bool {
+        // orderings decode what they compare, so the encodings are validated first
+        let valid =
+            |order: &Option<DynType>, data| order.as_ref().is_none_or(|ty| ty.validate(data));
+        if !valid(&self.key, key) || !self.value.as_ref().is_none_or(|order| valid(order, value)) {
+            return false;
+        }
+
+        let in_order = match &self.previous {
+            None => true,
+            Some((previous_key, previous_value)) => match compare(&self.key, previous_key, key) {
+                Some(Ordering::Less) | None => true,
+                Some(Ordering::Equal) => self.value.as_ref().is_some_and(|order| {
+                    compare(order, previous_value, value).is_none_or(Ordering::is_lt)
+                }),
+                Some(Ordering::Greater) => false,
+            },
+        };
+
+        let (previous_key, previous_value) = self.previous.get_or_insert_default();
+        previous_key.clear();
+        previous_key.extend_from_slice(key);
+        previous_value.clear();
+        previous_value.extend_from_slice(value);
+        in_order
+    }
+}
+
+/// The ordering of `type_name`, if this build orders it exactly as redb does.
+fn ordering(type_name: &TypeName) -> Option<DynType> {
+    DynType::from_name(type_name.name())
+        .filter(|ty| ty.is_key() && ty.type_name().is_ok_and(|name| name == *type_name))
+}
+
+/// Orders two encodings, or with an unknown ordering only tells whether they are the same.
+fn compare(order: &Option<DynType>, data1: &[u8], data2: &[u8]) -> Option<Ordering> {
+    match order {
+        Some(ty) => Some(ty.compare(data1, data2)),
+        None => (data1 == data2).then_some(Ordering::Equal),
+    }
+}
+
+fn append_order(pending: &RefCell<Vec<u8>>, data1: &[u8], data2: &[u8]) -> Ordering {
+    if data1 == data2 {
+        return Ordering::Equal;
+    }
+
+    let pending = pending.borrow();
+    if data2 == pending.as_slice() {
+        Ordering::Less
+    } else if data1 == pending.as_slice() {
+        Ordering::Greater
+    } else {
+        data1.cmp(data2)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RawKey;
+
+impl Value for RawKey {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        with_current(|schema| schema.key_width)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+
+    fn type_name() -> TypeName {
+        with_current(|schema| schema.key_type.clone())
+    }
+}
+
+impl Key for RawKey {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        PENDING_KEY.with(|pending| append_order(pending, data1, data2))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RawValue;
+
+impl Value for RawValue {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        with_current(|schema| schema.value_width)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+
+    fn type_name() -> TypeName {
+        with_current(|schema| schema.value_type.clone())
+    }
+}
+
+// multimap values are ordered within their key, so they need the same treatment as keys
+impl Key for RawValue {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        PENDING_VALUE.with(|pending| append_order(pending, data1, data2))
+    }
+}
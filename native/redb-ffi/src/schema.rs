@@ -0,0 +1,361 @@
+//! Records which key type, value type and host encoding each table was written with, which tables
+//! store their values through a codec, and the database-wide schema version migrations bring it
+//! to, in reserved tables that travel with the database.
+
+use redb::{
+    ReadTransaction, ReadableTable, TableDefinition, TableError, TableHandle, WriteTransaction,
+};
+
+// every table this module manages starts with it, and hosts may not use it
+const RESERVED_PREFIX: &str = "$redb.";
+
+const TABLE_NAME: &str = "$redb.schema";
+
+// table name -> (key type, value type, encoding, version)
+const TABLE: TableDefinition<&str, (&str, &str, &str, u32)> = TableDefinition::new(TABLE_NAME);
+
+// names of the tables whose values all carry a codec header
+const CODEC_TABLE_NAME: &str = "$redb.codec";
+const CODEC_TABLE: TableDefinition<&str, ()> = TableDefinition::new(CODEC_TABLE_NAME);
+
+const META_TABLE_NAME: &str = "$redb.meta";
+const META_TABLE: TableDefinition<&str, u32> = TableDefinition::new(META_TABLE_NAME);
+const VERSION_KEY: &str = "schema_version";
+
+/// Encoding recorded for tables opened with type descriptors, whose keys and values are in redb's
+/// own encoding.
+pub(crate) const TYPED_ENCODING: &str = "redb";
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TableSchema {
+    pub key_type: String,
+    pub value_type: String,
+    pub encoding: String,
+    pub version: u32,
+}
+
+/// The fields of a schema to check; `None` matches anything.
+pub(crate) struct SchemaFilter<'a> {
+    pub key_type: Option<&'a str>,
+    pub value_type: Option<&'a str>,
+    pub encoding: Option<&'a str>,
+    pub version: u32,
+}
+
+impl TableSchema {
+    pub(crate) fn has_types(&self, key_type: &str, value_type: &str) -> bool {
+        self.key_type == key_type && self.value_type == value_type
+    }
+
+    pub(crate) fn matches(&self, filter: &SchemaFilter) -> bool {
+        let field = |expected: Option<&str>, actual: &str| expected.is_none_or(|e| e == actual);
+        field(filter.key_type, &self.key_type)
+            && field(filter.value_type, &self.value_type)
+            && field(filter.encoding, &self.encoding)
+            && filter.version == self.version
+    }
+}
+
+/// Whether `name` belongs to the native layer rather than the host.
+pub(crate) fn is_reserved(name: &str) -> bool {
+    name.starts_with(RESERVED_PREFIX)
+}
+
+fn lookup(
+    table: &impl ReadableTable<&'static str, (&'static str, &'static str, &'static str, u32)>,
+    name: &str,
+) -> Result<Option<TableSchema>, redb::Error> {
+    Ok(table.get(name)?.map(|guard| {
+        let (key_type, value_type, encoding, version) = guard.value();
+        TableSchema {
+            key_type: key_type.to_owned(),
+            value_type: value_type.to_owned(),
+            encoding: encoding.to_owned(),
+            version,
+        }
+    }))
+}
+
+fn exists(tx: &WriteTransaction, table_name: &str) -> Result<bool, redb::Error> {
+    Ok(tx.list_tables()?.any(|handle| handle.name() == table_name))
+}
+
+pub(crate) fn set(
+    tx: &WriteTransaction,
+    name: &str,
+    schema: &TableSchema,
+) -> Result<(), redb::Error> {
+    let mut table = tx.open_table(TABLE)?;
+    table.insert(
+        name,
+        (
+            schema.key_type.as_str(),
+            schema.value_type.as_str(),
+            schema.encoding.as_str(),
+            schema.version,
+        ),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn get(tx: &ReadTransaction, name: &str) -> Result<Option<TableSchema>, redb::Error> {
+    match tx.open_table(TABLE) {
+        Ok(table) => lookup(&table, name),
+        Err(TableError::TableDoesNotExist(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Like `get`, without creating the reserved table in a write transaction.
+pub(crate) fn get_for_write(
+    tx: &WriteTransaction,
+    name: &str,
+) -> Result<Option<TableSchema>, redb::Error> {
+    if !exists(tx, TABLE_NAME)? {
+        return Ok(None);
+    }
+    lookup(&tx.open_table(TABLE)?, name)
+}
+
+/// Drops the records of a deleted table.
+pub(crate) fn remove(tx: &WriteTransaction, name: &str) -> Result<(), redb::Error> {
+    if exists(tx, TABLE_NAME)? {
+        tx.open_table(TABLE)?.remove(name)?;
+    }
+    if exists(tx, CODEC_TABLE_NAME)? {
+        tx.open_table(CODEC_TABLE)?.remove(name)?;
+    }
+    Ok(())
+}
+
+/// Moves the records of a renamed table to its new name.
+pub(crate) fn rename(
+    tx: &WriteTransaction,
+    old_name: &str,
+    new_name: &str,
+) -> Result<(), redb::Error> {
+    let schema = get_for_write(tx, old_name)?;
+    let codec = has_codec_for_write(tx, old_name)?;
+    remove(tx, old_name)?;
+    if let Some(schema) = schema {
+        set(tx, new_name, &schema)?;
+    }
+    if codec {
+        set_codec(tx, new_name)?;
+    }
+    Ok(())
+}
+
+/// Whether the values of `name` were written through a codec.
+pub(crate) fn has_codec(tx: &ReadTransaction, name: &str) -> Result<bool, redb::Error> {
+    match tx.open_table(CODEC_TABLE) {
+        Ok(table) => Ok(table.get(name)?.is_some()),
+        Err(TableError::TableDoesNotExist(_)) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn has_codec_for_write(tx: &WriteTransaction, name: &str) -> Result<bool, redb::Error> {
+    if !exists(tx, CODEC_TABLE_NAME)? {
+        return Ok(false);
+    }
+    Ok(tx.open_table(CODEC_TABLE)?.get(name)?.is_some())
+}
+
+pub(crate) fn set_codec(tx: &WriteTransaction, name: &str) -> Result<(), redb::Error> {
+    tx.open_table(CODEC_TABLE)?.insert(name, ())?;
+    Ok(())
+}
+
+/// The database schema version, 0 until a migration sets one.
+pub(crate) fn version(tx: &ReadTransaction) -> Result<u32, redb::Error> {
+    match tx.open_table(META_TABLE) {
+        Ok(table) => Ok(table.get(VERSION_KEY)?.map_or(0, |guard| guard.value())),
+        Err(TableError::TableDoesNotExist(_)) => Ok(0),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn version_for_write(tx: &WriteTransaction) -> Result<u32, redb::Error> {
+    if !exists(tx, META_TABLE_NAME)? {
+        return Ok(0);
+    }
+    let table = tx.open_table(META_TABLE)?;
+    Ok(table.get(VERSION_KEY)?.map_or(0, |guard| guard.value()))
+}
+
+pub(crate) fn set_version(tx: &WriteTransaction, version: u32) -> Result<(), redb::Error> {
+    tx.open_table(META_TABLE)?.insert(VERSION_KEY, version)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn in_memory_database() -> *mut c_void {
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        db
+    }
+
+    extern "C" fn succeed(_: *mut c_void, _: *mut c_void) -> i32 {
+        REDB_OK
+    }
+
+    // leaves a table open on the transaction and fails
+    extern "C" fn fail_with_open_table(_: *mut c_void, tx: *mut c_void) -> i32 {
+        let mut table = ptr::null_mut();
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"t".as_ptr(), &mut table),
+            REDB_OK
+        );
+        REDB_ERROR_CODEC
+    }
+
+    fn step(
+        version: u32,
+        migrate: extern "C" fn(*mut c_void, *mut c_void) -> i32,
+    ) -> redb_migration {
+        redb_migration {
+            version,
+            user_data: ptr::null_mut(),
+            migrate,
+        }
+    }
+
+    fn read_schema(tx: *mut c_void, name: &std::ffi::CStr) -> (i32, String) {
+        let mut schema = redb_table_schema {
+            key_type: ptr::null(),
+            value_type: ptr::null(),
+            encoding: ptr::null(),
+            version: 0,
+        };
+        let code = redb_write_tx_get_table_schema(tx, name.as_ptr(), &mut schema);
+        if code != REDB_OK {
+            return (code, String::new());
+        }
+        let key_type = unsafe { std::ffi::CStr::from_ptr(schema.key_type) };
+        let key_type = key_type.to_str().unwrap().to_owned();
+        for field in [schema.key_type, schema.value_type, schema.encoding] {
+            redb_free_string(field.cast_mut());
+        }
+        (code, key_type)
+    }
+
+    #[test]
+    fn keeps_hosts_out_of_reserved_tables() {
+        let db = in_memory_database();
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_table(tx, c"$redb.schema".as_ptr(), &mut table),
+            REDB_ERROR_RESERVED_TABLE_NAME
+        );
+        assert_eq!(
+            redb_write_tx_delete_table(tx, c"$redb.meta".as_ptr()),
+            REDB_ERROR_RESERVED_TABLE_NAME
+        );
+        assert_eq!(
+            redb_write_tx_rename_table(tx, c"t".as_ptr(), c"$redb.t".as_ptr()),
+            REDB_ERROR_RESERVED_TABLE_NAME
+        );
+        assert_eq!(
+            read_schema(tx, c"$redb.schema").0,
+            REDB_ERROR_RESERVED_TABLE_NAME
+        );
+        assert_eq!(redb_write_tx_abort(tx), REDB_OK);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn rejects_schema_fields_that_are_not_utf8() {
+        let db = in_memory_database();
+        let mut tx = ptr::null_mut();
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        let schema = redb_table_schema {
+            key_type: c"u64".as_ptr(),
+            value_type: c"\xff".as_ptr(),
+            encoding: c"redb".as_ptr(),
+            version: 1,
+        };
+        assert_eq!(
+            redb_write_tx_set_table_schema(tx, c"t".as_ptr(), &schema),
+            REDB_ERROR_INVALID_ENCODING
+        );
+        assert_eq!(
+            redb_write_tx_validate_table_schema(tx, c"t".as_ptr(), &schema),
+            REDB_ERROR_INVALID_ENCODING
+        );
+        assert_eq!(redb_write_tx_abort(tx), REDB_OK);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn typed_opens_record_and_check_their_types() {
+        let db = in_memory_database();
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_numeric_table(tx, c"t".as_ptr(), redb_numeric_key::U64, &mut table),
+            REDB_OK
+        );
+        redb_free_table(table);
+        assert_eq!(read_schema(tx, c"t"), (REDB_OK, "u64".to_owned()));
+
+        assert_eq!(
+            redb_write_tx_open_numeric_table(tx, c"t".as_ptr(), redb_numeric_key::U32, &mut table),
+            REDB_ERROR_SCHEMA_MISMATCH
+        );
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_read_tx_open_numeric_table(tx, c"t".as_ptr(), redb_numeric_key::I64, &mut table),
+            REDB_ERROR_SCHEMA_MISMATCH
+        );
+        redb_free_read_transaction(tx);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn failed_steps_release_the_write_transaction() {
+        let db = in_memory_database();
+        let mut version = u32::MAX;
+        let steps = [step(1, succeed), step(2, fail_with_open_table)];
+        assert_eq!(
+            redb_migrate(db, steps.as_ptr(), steps.len(), &mut version),
+            REDB_ERROR_CODEC
+        );
+        assert_eq!(version, 0);
+
+        // the failed run neither committed nor kept blocking writers
+        let mut tx = ptr::null_mut();
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        redb_free_write_transaction(tx);
+        assert_eq!(redb_migrate(db, steps.as_ptr(), 1, &mut version), REDB_OK);
+        assert_eq!(version, 1);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn reports_databases_newer_than_the_steps() {
+        let db = in_memory_database();
+        let mut version = 0;
+        let steps = [step(1, succeed), step(2, succeed)];
+        assert_eq!(redb_migrate(db, steps.as_ptr(), 2, &mut version), REDB_OK);
+        assert_eq!(version, 2);
+
+        assert_eq!(
+            redb_migrate(db, steps.as_ptr(), 1, &mut version),
+            REDB_ERROR_SCHEMA_VERSION_TOO_NEW
+        );
+        assert_eq!(redb_migrate(db, ptr::null(), 0, &mut version), REDB_OK);
+        assert_eq!(version, 2);
+        redb_free_database(db);
+    }
+}
@@ -0,0 +1,216 @@
+//! Round-trips redb `TypeName`s through their serialized form.
+//!
+//! redb only constructs internal type names for its own types, so a name read back from a dump
+//! is rebuilt by instantiating the matching built-in type with placeholder `Slot` components
+//! whose names are supplied at runtime.
+
+use redb::{Legacy, TypeName, Value};
+use std::cell::RefCell;
+
+pub(crate) const INTERNAL: u8 = 1;
+pub(crate) const USER_DEFINED: u8 = 2;
+pub(crate) const INTERNAL2: u8 = 3;
+
+const MAX_SLOTS: usize = 12;
+
+/// Returns the classification `resolve` rebuilds `type_name` from, if this build can rebuild it.
+/// redb keeps the classification private, so it is found by rebuilding the name each way and
+/// comparing the results.
+pub(crate) fn classification(type_name: &TypeName) -> Option<u8> {
+    [USER_DEFINED, INTERNAL, INTERNAL2]
+        .into_iter()
+        .find(|&classification| {
+            resolve(classification, type_name.name()).as_ref() == Some(type_name)
+        })
+}
+
+/// Rebuilds the `TypeName` with the given classification and name, if redb can produce it.
+pub(crate) fn resolve(classification: u8, name: &str) -> Option<TypeName> {
+    let type_name = match classification {
+        USER_DEFINED => return Some(TypeName::new(name)),
+        INTERNAL => resolve_internal(name, false)?,
+        INTERNAL2 => resolve_internal(name, true)?,
+        _ => return None,
+    };
+
+    (type_name.name() == name).then_some(type_name)
+}
+
+thread_local! {
+    static SLOTS: RefCell<Vec<(TypeName, Option<usize>)>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug)]
+struct Slot<const I: usize>;
+
+impl<const I: usize> Value for Slot<I> {
+    type SelfType<'a> = ();
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        SLOTS.with(|slots| slots.borrow()[I].1)
+    }
+
+    fn from_bytes<'a>(_data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+    }
+
+    fn as_bytes<'a, 'b: 'a>(_value: &'a Self::SelfType<'b>) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        &[]
+    }
+
+    fn type_name() -> TypeName {
+        SLOTS.with(|slots| slots.borrow()[I].0.clone())
+    }
+}
+
+fn with_slots(components: &[&str], fixed: bool, f: impl FnOnce() -> TypeName) -> TypeName {
+    let width = fixed.then_some(1);
+    SLOTS.with(|slots| {
+        *slots.borrow_mut() = components
+            .iter()
+            .map(|name| (TypeName::new(name), width))
+            .collect();
+    });
+    let type_name = f();
+    SLOTS.with(|slots| slots.borrow_mut().clear());
+    type_name
+}
+
+fn leaf(name: &str) -> Option<TypeName> {
+    macro_rules! leaf_types {
+        ($($t:ty),+) => {
+            [$(<$t>::type_name()),+]
+        };
+    }
+
+    leaf_types!(
+        (),
+        bool,
+        char,
+        &str,
+        String,
+        &[u8],
+        u8,
+        u16,
+        u32,
+        u64,
+        u128,
+        i8,
+        i16,
+        i32,
+        i64,
+        i128,
+        f32,
+        f64
+    )
+    .into_iter()
+    .find(|type_name| type_name.name() == name)
+}
+
+/// Splits `inner` at commas that are not nested inside brackets.
+pub(crate) fn split_top_level(inner: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            ')' | '>' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&inner[start..]);
+    parts
+}
+
+fn resolve_internal(name: &str, variable_tuple: bool) -> Option<TypeName> {
+    if let Some(type_name) = leaf(name) {
+        return Some(type_name);
+    }
+
+    if let Some(inner) = name
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+    {
+        return Some(with_slots(&[inner], true, Option::<Slot<0>>::type_name));
+    }
+
+    if let Some(inner) = name.strip_prefix("Vec<").and_then(|s| s.strip_suffix('>')) {
+        return Some(with_slots(&[inner], true, Vec::<Slot<0>>::type_name));
+    }
+
+    if let Some(inner) = name.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        let (element, len) = inner.rsplit_once(';')?;
+        return resolve_array(element, len.parse().ok()?);
+    }
+
+    let inner = name.strip_prefix('(')?.strip_suffix(')')?;
+    if let Some(element) = inner.strip_suffix(',')
+        && split_top_level(element).len() == 1
+    {
+        return Some(with_slots(&[element], true, <(Slot<0>,)>::type_name));
+    }
+
+    let components = split_top_level(inner);
+    if components.len() < 2 || components.len() > MAX_SLOTS {
+        return None;
+    }
+
+    // fixed width tuples and all legacy tuples use the original classification
+    let fixed = !variable_tuple;
+    Some(with_slots(&components, fixed, || {
+        macro_rules! tuple_type_name {
+            ($($n:literal => ($($i:literal),+)),+) => {
+                match components.len() {
+                    $(
+                        $n if variable_tuple => <($(Slot<$i>,)+)>::type_name(),
+                        $n => <Legacy<($(Slot<$i>,)+)>>::type_name(),
+                    )+
+                    _ => unreachable!(),
+                }
+            };
+        }
+
+        tuple_type_name!(
+            2 => (0, 1),
+            3 => (0, 1, 2),
+            4 => (0, 1, 2, 3),
+            5 => (0, 1, 2, 3, 4),
+            6 => (0, 1, 2, 3, 4, 5),
+            7 => (0, 1, 2, 3, 4, 5, 6),
+            8 => (0, 1, 2, 3, 4, 5, 6, 7),
+            9 => (0, 1, 2, 3, 4, 5, 6, 7, 8),
+            10 => (0, 1, 2, 3, 4, 5, 6, 7, 8, 9),
+            11 => (0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10),
+            12 => (0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11)
+        )
+    }))
+}
+
+fn resolve_array(element: &str, len: usize) -> Option<TypeName> {
+    macro_rules! array_type_name {
+        ($($n:literal),+) => {
+            match len {
+                $($n => Some(with_slots(&[element], true, <[Slot<0>; $n]>::type_name)),)+
+                _ => None,
+            }
+        };
+    }
+
+    array_type_name!(
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+        25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+        48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 128, 256, 512, 1024,
+        2048, 4096
+    )
+}
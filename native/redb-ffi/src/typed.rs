@@ -0,0 +1,875 @@
+//! Tables whose key and value types are described by the host at runtime.
+//!
+//! Keys and values cross the FFI boundary in redb's own binary encoding, so a table opened here
+//! is interchangeable with one a Rust program opens with the matching `TableDefinition`. As in
+//! `raw`, redb reads type names, fixed widths and key ordering through static trait functions, so
+//! `TypedKey` and `TypedValue` take them from a thread-local schema that is installed around
+//! every table operation.
+
+use crate::{
+    comparator::{self, HostComparator},
+    redb_type_descriptor, redb_type_kind, type_names,
+};
+use redb::{Key, TypeName, Value};
+use std::{cell::RefCell, cmp::Ordering, sync::Arc};
+
+const MAX_DEPTH: usize = 16;
+const MAX_TUPLE_LEN: usize = 12;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum DynType {
+    Unit,
+    Bool,
+    Unsigned(usize),
+    Signed(usize),
+    F32,
+    F64,
+    Str,
+    Bytes,
+    Array(Box<DynType>, usize),
+    Tuple(Vec<DynType>),
+    /// A user-defined type, ordered by the comparator registered under its name if it has one
+    Custom(String, Option<Arc<HostComparator>>),
+    /// An integer, string or byte string encoded as itself but ordered in reverse
+    Descending(Box<DynType>),
+}
+
+#[derive(Debug)]
+pub(crate) struct TypeError;
+
+impl DynType {
+    /// Reads a host type descriptor.
+    ///
+    /// # Safety
+    /// `descriptor` and the element descriptors it points to must be valid.
+    pub(crate) unsafe fn from_descriptor(
+        descriptor: *const redb_type_descriptor,
+    ) -> Result<Self, TypeError> {
+        unsafe { Self::from_descriptor_at(descriptor, 0) }
+    }
+
+    unsafe fn from_descriptor_at(
+        descriptor: *const redb_type_descriptor,
+        depth: usize,
+    ) -> Result<Self, TypeError> {
+        // also stops descriptors that refer back to themselves
+        if descriptor.is_null() || depth > MAX_DEPTH {
+            return Err(TypeError);
+        }
+        let descriptor = unsafe { &*descriptor };
+
+        Ok(match descriptor.kind {
+            redb_type_kind::Unit => DynType::Unit,
+            redb_type_kind::Bool => DynType::Bool,
+            redb_type_kind::U8 => DynType::Unsigned(1),
+            redb_type_kind::U16 => DynType::Unsigned(2),
+            redb_type_kind::U32 => DynType::Unsigned(4),
+            redb_type_kind::U64 => DynType::Unsigned(8),
+            redb_type_kind::U128 => DynType::Unsigned(16),
+            redb_type_kind::I8 => DynType::Signed(1),
+            redb_type_kind::I16 => DynType::Signed(2),
+            redb_type_kind::I32 => DynType::Signed(4),
+            redb_type_kind::I64 => DynType::Signed(8),
+            redb_type_kind::I128 => DynType::Signed(16),
+            redb_type_kind::F32 => DynType::F32,
+            redb_type_kind::F64 => DynType::F64,
+            redb_type_kind::Str => DynType::Str,
+            redb_type_kind::Bytes => DynType::Bytes,
+            redb_type_kind::Array => {
+                let element = unsafe { Self::from_descriptor_at(descriptor.elements, depth + 1)? };
+                DynType::Array(Box::new(element), descriptor.array_len)
+            }
+            redb_type_kind::Tuple => {
+                if descriptor.element_count == 0 || descriptor.element_count > MAX_TUPLE_LEN {
+                    return Err(TypeError);
+                }
+                let elements = (0..descriptor.element_count)
+                    .map(|i| unsafe {
+                        Self::from_descriptor_at(descriptor.elements.add(i), depth + 1)
+                    })
+                    .collect::<Result<_, _>>()?;
+                DynType::Tuple(elements)
+            }
+            redb_type_kind::Custom => {
+                if descriptor.name.is_null() {
+                    return Err(TypeError);
+                }
+                let name = unsafe { std::ffi::CStr::from_ptr(descriptor.name) };
+                let name = name.to_str().map_err(|_| TypeError)?;
+                if name.is_empty() {
+                    return Err(TypeError);
+                }
+                DynType::Custom(name.to_owned(), comparator::lookup(name))
+            }
+            redb_type_kind::Descending => {
+                let inner = unsafe { Self::from_descriptor_at(descriptor.elements, depth + 1)? };
+                if !matches!(
+                    inner,
+                    DynType::Unsigned(_) | DynType::Signed(_) | DynType::Str | DynType::Bytes
+                ) {
+                    return Err(TypeError);
+                }
+                DynType::Descending(Box::new(inner))
+            }
+        })
+    }
+
+    fn fixed_width(&self) -> Option<usize> {
+        match self {
+            DynType::Unit => Some(0),
+            DynType::Bool => Some(1),
+            DynType::Unsigned(width) | DynType::Signed(width) => Some(*width),
+            DynType::F32 => Some(4),
+            DynType::F64 => Some(8),
+            DynType::Str | DynType::Bytes | DynType::Custom(..) => None,
+            DynType::Array(element, len) => element.fixed_width().map(|width| width * len),
+            DynType::Tuple(elements) => elements.iter().map(DynType::fixed_width).sum(),
+            DynType::Descending(inner) => inner.fixed_width(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            DynType::Unit => "()".to_owned(),
+            DynType::Bool => "bool".to_owned(),
+            DynType::Unsigned(width) => format!("u{}", width * 8),
+            DynType::Signed(width) => format!("i{}", width * 8),
+            DynType::F32 => "f32".to_owned(),
+            DynType::F64 => "f64".to_owned(),
+            DynType::Str => "&str".to_owned(),
+            DynType::Bytes => "&[u8]".to_owned(),
+            DynType::Array(element, len) => format!("[{};{len}]", element.name()),
+            DynType::Tuple(elements) if elements.len() == 1 => format!("({},)", elements[0].name()),
+            DynType::Tuple(elements) => {
+                let names: Vec<_> = elements.iter().map(DynType::name).collect();
+                format!("({})", names.join(","))
+            }
+            DynType::Custom(name, _) => name.clone(),
+            DynType::Descending(inner) => format!("Descending<{}>", inner.name()),
+        }
+    }
+
+    /// Parses a name produced by `name`. Names of types this module cannot describe are taken to
+    /// be custom types.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        let integer_width = |prefix| {
+            name.strip_prefix(prefix)
+                .and_then(|bits| bits.parse::<usize>().ok())
+                .filter(|bits| matches!(bits, 8 | 16 | 32 | 64 | 128))
+                .map(|bits| bits / 8)
+        };
+
+        Some(match name {
+            "()" => DynType::Unit,
+            "bool" => DynType::Bool,
+            "f32" => DynType::F32,
+            "f64" => DynType::F64,
+            "&str" => DynType::Str,
+            "&[u8]" => DynType::Bytes,
+            _ => {
+                if let Some(width) = integer_width('u') {
+                    DynType::Unsigned(width)
+                } else if let Some(width) = integer_width('i') {
+                    DynType::Signed(width)
+                } else if let Some(inner) = name
+                    .strip_prefix("Descending<")
+                    .and_then(|inner| inner.strip_suffix('>'))
+                {
+                    DynType::Descending(Box::new(Self::from_name(inner)?))
+                } else if let Some(inner) = name.strip_prefix('[').and_then(|s| s.strip_suffix(']'))
+                {
+                    let (element, len) = inner.rsplit_once(';')?;
+                    DynType::Array(Box::new(Self::from_name(element)?), len.parse().ok()?)
+                } else if let Some(inner) = name.strip_prefix('(').and_then(|s| s.strip_suffix(')'))
+                {
+                    let elements = match inner.strip_suffix(',') {
+                        Some(element) => vec![element],
+                        None => type_names::split_top_level(inner),
+                    };
+                    if elements.len() > MAX_TUPLE_LEN {
+                        return None;
+                    }
+                    let elements = elements.into_iter().map(Self::from_name);
+                    DynType::Tuple(elements.collect::<Option<_>>()?)
+                } else {
+                    DynType::Custom(name.to_owned(), comparator::lookup(name))
+                }
+            }
+        })
+    }
+
+    /// Builds the `TypeName` redb gives the equivalent Rust type.
+    pub(crate) fn type_name(&self) -> Result<TypeName, TypeError> {
+        let classification = match self {
+            // tuples of two or more elements changed their encoding when they are variable width
+            DynType::Tuple(elements) if elements.len() > 1 && self.fixed_width().is_none() => {
+                type_names::INTERNAL2
+            }
+            DynType::Custom(..) | DynType::Descending(_) => type_names::USER_DEFINED,
+            _ => type_names::INTERNAL,
+        };
+        type_names::resolve(classification, &self.name()).ok_or(TypeError)
+    }
+
+    /// Whether redb implements `Key` for the equivalent Rust type.
+    pub(crate) fn is_key(&self) -> bool {
+        match self {
+            DynType::F32 | DynType::F64 => false,
+            DynType::Array(element, _) => element.is_key(),
+            DynType::Tuple(elements) => elements.iter().all(DynType::is_key),
+            DynType::Custom(_, comparator) => comparator.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Checks that `data` is a well-formed encoding, so redb never panics decoding it.
+    pub(crate) fn validate(&self, data: &[u8]) -> bool {
+        match self {
+            DynType::Bool => matches!(data, [0 | 1]),
+            DynType::Str => std::str::from_utf8(data).is_ok(),
+            DynType::Bytes | DynType::Custom(..) => true,
+            DynType::Descending(inner) => inner.validate(data),
+            DynType::Array(element, len) => match element.fixed_width() {
+                Some(width) => {
+                    data.len() == width * len
+                        && data.chunks(width.max(1)).all(|e| element.validate(e))
+                }
+                None => array_elements(data, *len)
+                    .is_some_and(|elements| elements.iter().all(|e| element.validate(e))),
+            },
+            DynType::Tuple(elements) => tuple_elements(elements, data).is_some_and(|slices| {
+                elements
+                    .iter()
+                    .zip(slices)
+                    .all(|(element, slice)| element.validate(slice))
+            }),
+            _ => Some(data.len()) == self.fixed_width(),
+        }
+    }
+
+    /// The encoding that sorts before every other, if the type has one.
+    fn min_encoding(&self) -> Option<Vec<u8>> {
+        match self {
+            DynType::Unit | DynType::Str | DynType::Bytes => Some(Vec::new()),
+            DynType::Bool => Some(vec![0]),
+            DynType::Unsigned(width) => Some(vec![0; *width]),
+            DynType::Signed(width) => {
+                let mut data = vec![0; *width];
+                data[width - 1] = 0x80;
+                Some(data)
+            }
+            DynType::F32 | DynType::F64 | DynType::Custom(..) => None,
+            DynType::Descending(inner) => match **inner {
+                DynType::Unsigned(width) => Some(vec![0xff; width]),
+                DynType::Signed(width) => {
+                    let mut data = vec![0xff; width];
+                    data[width - 1] = 0x7f;
+                    Some(data)
+                }
+                // strings have no largest value
+                _ => None,
+            },
+            DynType::Array(element, len) => {
+                let min = element.min_encoding()?;
+                let mut data = Vec::new();
+                if element.fixed_width().is_none() {
+                    for i in 1..=*len {
+                        let end = u32::try_from(len * 4 + min.len() * i).ok()?;
+                        data.extend_from_slice(&end.to_le_bytes());
+                    }
+                }
+                for _ in 0..*len {
+                    data.extend_from_slice(&min);
+                }
+                Some(data)
+            }
+            DynType::Tuple(elements) => {
+                let mins: Vec<_> = elements
+                    .iter()
+                    .map(DynType::min_encoding)
+                    .collect::<Option<_>>()?;
+                let slices: Vec<_> = mins.iter().map(Vec::as_slice).collect();
+                encode_tuple(elements, &slices)
+            }
+        }
+    }
+
+    /// Orders two valid encodings the way redb orders the equivalent Rust type.
+    pub(crate) fn compare(&self, data1: &[u8], data2: &[u8]) -> Ordering {
+        match self {
+            DynType::Unit => Ordering::Equal,
+            DynType::Unsigned(_) => data1.iter().rev().cmp(data2.iter().rev()),
+            DynType::Signed(_) => {
+                let (high1, low1) = data1.split_last().unwrap();
+                let (high2, low2) = data2.split_last().unwrap();
+                (*high1 as i8)
+                    .cmp(&(*high2 as i8))
+                    .then_with(|| low1.iter().rev().cmp(low2.iter().rev()))
+            }
+            DynType::Bool | DynType::Str | DynType::Bytes => data1.cmp(data2),
+            DynType::F32 | DynType::F64 => unreachable!("floats are not keys"),
+            DynType::Custom(_, comparator) => comparator
+                .as_ref()
+                .expect("custom keys have a comparator")
+                .compare(data1, data2),
+            DynType::Descending(inner) => inner.compare(data2, data1),
+            DynType::Array(element, len) => match element.fixed_width() {
+                Some(width) => (0..*len)
+                    .map(|i| {
+                        let range = width * i..width * (i + 1);
+                        element.compare(&data1[range.clone()], &data2[range])
+                    })
+                    .find(|order| order.is_ne())
+                    .unwrap_or(Ordering::Equal),
+                None => {
+                    let elements1 = array_elements(data1, *len).unwrap();
+                    let elements2 = array_elements(data2, *len).unwrap();
+                    compare_elements(std::iter::repeat(&**element), &elements1, &elements2)
+                }
+            },
+            DynType::Tuple(elements) => {
+                let slices1 = tuple_elements(elements, data1).unwrap();
+                let slices2 = tuple_elements(elements, data2).unwrap();
+                compare_elements(elements.iter(), &slices1, &slices2)
+            }
+        }
+    }
+}
+
+fn compare_elements<'a>(
+    elements: impl Iterator<Item = &'a DynType>,
+    slices1: &[&[u8]],
+    slices2: &[&[u8]],
+) -> Ordering {
+    elements
+        .zip(slices1.iter().zip(slices2))
+        .map(|(element, (data1, data2))| element.compare(data1, data2))
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Splits a variable width array, which starts with the u32 end offset of every element.
+fn array_elements(data: &[u8], len: usize) -> Option<Vec<&[u8]>> {
+    let mut start = len.checked_mul(4)?;
+    let mut elements = Vec::with_capacity(len);
+    for i in 0..len {
+        let end = u32::from_le_bytes(data.get(4 * i..4 * (i + 1))?.try_into().unwrap()) as usize;
+        elements.push(data.get(start..end)?);
+        start = end;
+    }
+    (start == data.len()).then_some(elements)
+}
+
+/// Splits a tuple. Fixed width tuples are concatenated; variable width ones are prefixed with the
+/// varint length of every variable width element but the last.
+fn tuple_elements<'a>(elements: &[DynType], data: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+    if elements.len() == 1 {
+        return Some(vec![data]);
+    }
+
+    let mut offset = 0;
+    let mut lens = Vec::with_capacity(elements.len());
+    for element in &elements[..elements.len() - 1] {
+        match element.fixed_width() {
+            Some(width) => lens.push(width),
+            None => {
+                let (len, read) = decode_varint_len(data.get(offset..)?)?;
+                lens.push(len);
+                offset += read;
+            }
+        }
+    }
+
+    let mut slices = Vec::with_capacity(elements.len());
+    for len in lens {
+        slices.push(data.get(offset..offset.checked_add(len)?)?);
+        offset += len;
+    }
+    // the last element takes the rest
+    slices.push(data.get(offset..)?);
+    Some(slices)
+}
+
+/// Joins valid element encodings into a tuple, the inverse of `tuple_elements`.
+fn encode_tuple(elements: &[DynType], slices: &[&[u8]]) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    for (element, slice) in elements.iter().zip(slices).take(elements.len() - 1) {
+        if element.fixed_width().is_none() {
+            encode_varint_len(slice.len(), &mut data)?;
+        }
+    }
+    for slice in slices {
+        data.extend_from_slice(slice);
+    }
+    Some(data)
+}
+
+fn encode_varint_len(len: usize, data: &mut Vec<u8>) -> Option<()> {
+    if len < 254 {
+        data.push(len as u8);
+    } else if let Ok(len) = u16::try_from(len) {
+        data.push(254);
+        data.extend_from_slice(&len.to_le_bytes());
+    } else {
+        data.push(255);
+        data.extend_from_slice(&u32::try_from(len).ok()?.to_le_bytes());
+    }
+    Some(())
+}
+
+fn decode_varint_len(data: &[u8]) -> Option<(usize, usize)> {
+    match *data.first()? {
+        len @ 0..=253 => Some((len as usize, 1)),
+        254 => Some((
+            u16::from_le_bytes(data.get(1..3)?.try_into().unwrap()) as usize,
+            3,
+        )),
+        255 => Some((
+            u32::from_le_bytes(data.get(1..5)?.try_into().unwrap()) as usize,
+            5,
+        )),
+    }
+}
+
+/// Encodes a composite key from the encodings of its components, if they are all valid.
+pub(crate) fn encode_composite(elements: &[DynType], parts: &[&[u8]]) -> Option<Vec<u8>> {
+    let valid = parts.len() == elements.len()
+        && elements
+            .iter()
+            .zip(parts)
+            .all(|(element, part)| element.validate(part));
+    if valid {
+        encode_tuple(elements, parts)
+    } else {
+        None
+    }
+}
+
+/// Splits a valid composite key into the encodings of its components.
+pub(crate) fn decode_composite<'a>(elements: &[DynType], data: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+    let parts = tuple_elements(elements, data)?;
+    elements
+        .iter()
+        .zip(&parts)
+        .all(|(element, part)| element.validate(part))
+        .then_some(parts)
+}
+
+/// Orders a composite key against a prefix of its components, `Equal` when the key starts with it.
+pub(crate) fn compare_prefix(elements: &[DynType], key: &[u8], prefix: &[&[u8]]) -> Ordering {
+    let parts = tuple_elements(elements, key).unwrap();
+    compare_elements(elements.iter(), &parts[..prefix.len()], prefix)
+}
+
+/// The smallest composite key that starts with `prefix`, if the remaining components have a
+/// smallest value.
+pub(crate) fn prefix_lower_bound(elements: &[DynType], prefix: &[&[u8]]) -> Option<Vec<u8>> {
+    let rest: Vec<_> = elements[prefix.len()..]
+        .iter()
+        .map(DynType::min_encoding)
+        .collect::<Option<_>>()?;
+    let mut parts = prefix.to_vec();
+    parts.extend(rest.iter().map(Vec::as_slice));
+    encode_tuple(elements, &parts)
+}
+
+#[derive(Debug)]
+pub(crate) struct TypedSchema {
+    pub key: DynType,
+    pub value: DynType,
+    key_type: TypeName,
+    value_type: TypeName,
+}
+
+impl TypedSchema {
+    pub(crate) fn new(key: DynType, value: DynType) -> Result<Self, TypeError> {
+        if !key.is_key() {
+            return Err(TypeError);
+        }
+        Ok(Self {
+            key_type: key.type_name()?,
+            value_type: value.type_name()?,
+            key,
+            value,
+        })
+    }
+
+    /// The names redb records for the key and value types.
+    pub(crate) fn type_names(&self) -> (&str, &str) {
+        (self.key_type.name(), self.value_type.name())
+    }
+}
+
+thread_local! {
+    static SCHEMA: RefCell<Option<Arc<TypedSchema>>> = const { RefCell::new(None) };
+}
+
+fn with_current<R>(f: impl FnOnce(&TypedSchema) -> R) -> R {
+    SCHEMA.with(|schema| {
+        f(schema
+            .borrow()
+            .as_ref()
+            .expect("typed table used without a schema"))
+    })
+}
+
+/// Runs `f` with `schema` installed for `TypedKey` and `TypedValue`.
+pub(crate) fn with_schema<R>(schema: &Arc<TypedSchema>, f: impl FnOnce() -> R) -> R {
+    let previous = SCHEMA.with(|current| current.replace(Some(schema.clone())));
+    let result = f();
+    SCHEMA.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+#[derive(Debug)]
+pub(crate) struct TypedKey;
+
+impl Value for TypedKey {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        with_current(|schema| schema.key.fixed_width())
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+
+    fn type_name() -> TypeName {
+        with_current(|schema| schema.key_type.clone())
+    }
+}
+
+impl Key for TypedKey {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        with_current(|schema| schema.key.compare(data1, data2))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TypedValue;
+
+impl Value for TypedValue {
+    type SelfType<'a> = &'a [u8];
+    type AsBytes<'a> = &'a [u8];
+
+    fn fixed_width() -> Option<usize> {
+        with_current(|schema| schema.value.fixed_width())
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> &'a [u8]
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a &'b [u8]) -> &'a [u8]
+    where
+        Self: 'b,
+    {
+        value
+    }
+
+    fn type_name() -> TypeName {
+        with_current(|schema| schema.value_type.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    fn descriptor(kind: redb_type_kind) -> redb_type_descriptor {
+        redb_type_descriptor {
+            kind,
+            elements: ptr::null(),
+            element_count: 0,
+            array_len: 0,
+            name: ptr::null(),
+        }
+    }
+
+    fn descending(inner: &redb_type_descriptor) -> redb_type_descriptor {
+        redb_type_descriptor {
+            elements: inner,
+            ..descriptor(redb_type_kind::Descending)
+        }
+    }
+
+    fn in_memory_database() -> *mut c_void {
+        let mut db = ptr::null_mut();
+        assert_eq!(
+            redb_create_database_from_bytes(ptr::null(), 0, ptr::null(), &mut db),
+            REDB_OK
+        );
+        db
+    }
+
+    fn insert_keys(db: *mut c_void, key_type: &redb_type_descriptor, keys: &[&[u8]]) {
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_typed_table(
+                tx,
+                c"t".as_ptr(),
+                key_type,
+                &descriptor(redb_type_kind::Bytes),
+                &mut table
+            ),
+            REDB_OK
+        );
+        for key in keys {
+            assert_eq!(
+                redb_insert(table, key.as_ptr(), key.len(), b"v".as_ptr(), 1),
+                REDB_OK
+            );
+        }
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+    }
+
+    /// Opens table `t` for reading, hands it to `range` for an iterator and collects its keys.
+    fn range_keys(
+        db: *mut c_void,
+        key_type: &redb_type_descriptor,
+        range: impl FnOnce(*mut c_void, *mut *mut c_void) -> i32,
+    ) -> Vec<Vec<u8>> {
+        let (mut tx, mut table, mut iter) = (ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_read(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_read_tx_open_typed_table(
+                tx,
+                c"t".as_ptr(),
+                key_type,
+                &descriptor(redb_type_kind::Bytes),
+                &mut table
+            ),
+            REDB_OK
+        );
+        assert_eq!(range(table, &mut iter), REDB_OK);
+
+        let mut keys = Vec::new();
+        loop {
+            let (mut key, mut value) = (redb_blob::EMPTY, redb_blob::EMPTY);
+            match redb_iter_next(iter, &mut key, &mut value) {
+                REDB_OK => {
+                    keys.push(unsafe { key.into_vec() });
+                    redb_free_blob(&mut value);
+                }
+                code => {
+                    assert_eq!(code, REDB_ERROR_KEY_NOT_FOUND);
+                    break;
+                }
+            }
+        }
+        redb_free_iter(iter);
+        redb_free_readonly_table(table);
+        redb_free_read_transaction(tx);
+        keys
+    }
+
+    fn bounded(start: &[u8], end: &[u8]) -> impl FnOnce(*mut c_void, *mut *mut c_void) -> i32 {
+        move |table, out| {
+            redb_range(
+                table,
+                start.as_ptr(),
+                start.len(),
+                end.as_ptr(),
+                end.len(),
+                out,
+            )
+        }
+    }
+
+    #[test]
+    fn descending_integers_range_largest_first() {
+        let db = in_memory_database();
+        let key_type = descending(&descriptor(redb_type_kind::U64));
+        let keys = [1u64, 256, 2, 300].map(u64::to_le_bytes);
+        insert_keys(db, &key_type, &keys.each_ref().map(|key| &key[..]));
+
+        let decode = |keys: Vec<Vec<u8>>| {
+            keys.iter()
+                .map(|key| u64::from_le_bytes(key.as_slice().try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let all = range_keys(db, &key_type, |table, out| redb_iter(table, out));
+        assert_eq!(decode(all), [300, 256, 2, 1]);
+        // the start bound is the larger key, as the table sorts
+        let (start, end) = (256u64.to_le_bytes(), 1u64.to_le_bytes());
+        let some = range_keys(db, &key_type, bounded(&start, &end));
+        assert_eq!(decode(some), [256, 2]);
+        redb_free_database(db);
+    }
+
+    #[test]
+    fn descending_strings_and_bytes_range_largest_first() {
+        for inner in [redb_type_kind::Str, redb_type_kind::Bytes] {
+            let db = in_memory_database();
+            let key_type = descending(&descriptor(inner));
+            insert_keys(db, &key_type, &[b"a", b"c", b"ab", b"b"]);
+
+            let all = range_keys(db, &key_type, |table, out| redb_iter(table, out));
+            assert_eq!(all, [&b"c"[..], b"b", b"ab", b"a"]);
+            let some = range_keys(db, &key_type, bounded(b"b", b"a"));
+            assert_eq!(some, [&b"b"[..], b"ab"]);
+            redb_free_database(db);
+        }
+    }
+
+    fn key_parts(parts: &[&[u8]]) -> Vec<redb_key_part> {
+        parts
+            .iter()
+            .map(|part| redb_key_part {
+                data: part.as_ptr(),
+                len: part.len(),
+            })
+            .collect()
+    }
+
+    fn composite_key(components: &[redb_type_descriptor], parts: &[&[u8]]) -> Vec<u8> {
+        let parts = key_parts(parts);
+        let mut key = redb_blob::EMPTY;
+        assert_eq!(
+            redb_encode_composite_key(
+                components.as_ptr(),
+                components.len(),
+                parts.as_ptr(),
+                &mut key
+            ),
+            REDB_OK
+        );
+        unsafe { key.into_vec() }
+    }
+
+    #[test]
+    fn prefix_ranges_match_whole_components() {
+        let db = in_memory_database();
+        let components = [
+            descriptor(redb_type_kind::U32),
+            descriptor(redb_type_kind::Bytes),
+            descriptor(redb_type_kind::U32),
+        ];
+        let key_type = redb_type_descriptor {
+            elements: components.as_ptr(),
+            element_count: components.len(),
+            ..descriptor(redb_type_kind::Tuple)
+        };
+        let (one, two) = (1u32.to_le_bytes(), 2u32.to_le_bytes());
+        let keys = [
+            composite_key(&components, &[&one, b"ab", &one]),
+            composite_key(&components, &[&one, b"ab", &two]),
+            composite_key(&components, &[&one, b"abc", &one]),
+            composite_key(&components, &[&two, b"ab", &one]),
+        ];
+        insert_keys(db, &key_type, &keys.each_ref().map(Vec::as_slice));
+
+        let prefix = |parts: &[&[u8]]| {
+            let parts = key_parts(parts);
+            range_keys(db, &key_type, move |table, out| {
+                redb_range_prefix(table, parts.as_ptr(), parts.len(), out)
+            })
+        };
+        assert!(prefix(&[&7u32.to_le_bytes()]).is_empty());
+        assert!(prefix(&[&one, b"a"]).is_empty());
+        // `ab` is a byte prefix of `abc`, but not a prefix of its component
+        assert_eq!(prefix(&[&one, b"ab"]), keys[..2]);
+        assert_eq!(prefix(&[&one, b"ab", &two]), keys[1..2]);
+        assert_eq!(prefix(&[&one]), keys[..3]);
+        redb_free_database(db);
+    }
+
+    /// Inserts `keys` into a new numeric table `t` through `insert`.
+    fn numeric_database<K: Copy>(
+        key_type: redb_numeric_key,
+        keys: &[K],
+        insert: extern "C" fn(*mut c_void, K, *const u8, usize) -> i32,
+    ) -> *mut c_void {
+        let db = in_memory_database();
+        let (mut tx, mut table) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(redb_begin_write(db, &mut tx), REDB_OK);
+        assert_eq!(
+            redb_write_tx_open_numeric_table(tx, c"t".as_ptr(), key_type, &mut table),
+            REDB_OK
+        );
+        for &key in keys {
+            assert_eq!(insert(table, key, b"v".as_ptr(), 1), REDB_OK);
+        }
+        redb_free_table(table);
+        assert_eq!(redb_write_tx_commit(tx), REDB_OK);
+        db
+    }
+
+    #[test]
+    fn integer_keys_sort_numerically() {
+        let key_type = descriptor(redb_type_kind::U64);
+        let db = numeric_database(
+            redb_numeric_key::U64,
+            &[256u64, 1, 65536, 2],
+            redb_insert_u64,
+        );
+        let decode = |keys: Vec<Vec<u8>>| {
+            keys.iter()
+                .map(|key| u64::from_le_bytes(key.as_slice().try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let all = range_keys(db, &key_type, |table, out| redb_iter(table, out));
+        assert_eq!(decode(all), [1, 2, 256, 65536]);
+        let some = range_keys(db, &key_type, |table, out| {
+            redb_range_u64(table, &2, &65536, out)
+        });
+        assert_eq!(decode(some), [2, 256]);
+        redb_free_database(db);
+
+        let key_type = descriptor(redb_type_kind::I64);
+        let db = numeric_database(
+            redb_numeric_key::I64,
+            &[-1i64, 256, -300, 1, 0],
+            redb_insert_i64,
+        );
+        let decode = |keys: Vec<Vec<u8>>| {
+            keys.iter()
+                .map(|key| i64::from_le_bytes(key.as_slice().try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let all = range_keys(db, &key_type, |table, out| redb_iter(table, out));
+        assert_eq!(decode(all), [-300, -1, 0, 1, 256]);
+        let some = range_keys(db, &key_type, |table, out| {
+            redb_range_i64(table, &-300, &1, out)
+        });
+        assert_eq!(decode(some), [-300, -1, 0]);
+        redb_free_database(db);
+
+        let key_type = descriptor(redb_type_kind::I32);
+        let db = numeric_database(
+            redb_numeric_key::I32,
+            &[-1i32, 256, -300, 1, 0],
+            redb_insert_i32,
+        );
+        let decode = |keys: Vec<Vec<u8>>| {
+            keys.iter()
+                .map(|key| i32::from_le_bytes(key.as_slice().try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let all = range_keys(db, &key_type, |table, out| redb_iter(table, out));
+        assert_eq!(decode(all), [-300, -1, 0, 1, 256]);
+        let some = range_keys(db, &key_type, |table, out| {
+            redb_range_i32(table, &-1, ptr::null(), out)
+        });
+        assert_eq!(decode(some), [-1, 0, 1, 256]);
+        redb_free_database(db);
+    }
+}
@@ -0,0 +1,139 @@
+//! Upgrades of database files written in older redb file formats.
+
+use crate::*;
+
+pub(crate) fn upgrade_database(path: &Path, dest_path: Option<&Path>, out_version: *mut u8) -> i32 {
+    let version = match redb::Database::open(path) {
+        Ok(_) => REDB_FILE_FORMAT_VERSION,
+        Err(redb::DatabaseError::UpgradeRequired(version)) => version,
+        Err(err) => return database_error_code(&err),
+    };
+
+    unsafe {
+        *out_version = version;
+    }
+
+    // format v1 predates redb 2.x and cannot be migrated by this build
+    if version != REDB_FILE_FORMAT_VERSION && version != 2 {
+        return REDB_ERROR_UPGRADE_REQUIRED;
+    }
+
+    let Some(dest_path) = dest_path else {
+        return upgrade_file(path, version);
+    };
+    // a failed upgrade leaves no copy behind
+    let code = match std::fs::copy(path, dest_path) {
+        Ok(_) => upgrade_file(dest_path, version),
+        Err(err) => file_error_code(&err),
+    };
+    if code != REDB_OK {
+        let _ = std::fs::remove_file(dest_path);
+    }
+    code
+}
+
+fn upgrade_file(path: &Path, version: u8) -> i32 {
+    if version == REDB_FILE_FORMAT_VERSION {
+        return REDB_OK;
+    }
+
+    // redb 2.x can read format v2 files and rewrite them as v3
+    let mut db = match redb2::Database::open(path) {
+        Ok(db) => db,
+        Err(redb2::DatabaseError::DatabaseAlreadyOpen) => return REDB_ERROR_DATABASE_ALREADY_OPEN,
+        Err(redb2::DatabaseError::RepairAborted) => return REDB_ERROR_REPAIR_ABORTED,
+        Err(redb2::DatabaseError::Storage(_)) => return REDB_ERROR_STORAGE_ERROR,
+        Err(_) => return REDB_ERROR_FILE_ERROR,
+    };
+
+    match db.upgrade() {
+        Ok(_) => REDB_OK,
+        Err(_) => REDB_ERROR_UPGRADE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::ptr;
+
+    const TABLE: redb2::TableDefinition<&str, &str> = redb2::TableDefinition::new("t");
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("redb-ffi-{name}-{}.redb", std::process::id()))
+    }
+
+    fn c_path(path: &Path) -> std::ffi::CString {
+        std::ffi::CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    fn create_v2(path: &Path) {
+        let db = redb2::Database::create(path).unwrap();
+        let tx = db.begin_write().unwrap();
+        tx.open_table(TABLE).unwrap().insert("k", "v").unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn read_entry(path: &Path) -> String {
+        let db = redb::Database::open(path).unwrap();
+        let tx = db.begin_read().unwrap();
+        let table = tx
+            .open_table(TableDefinition::<&str, &str>::new("t"))
+            .unwrap();
+        table.get("k").unwrap().unwrap().value().to_owned()
+    }
+
+    #[test]
+    fn upgrades_format_2_files() {
+        let (path, dest_path) = (temp_path("upgrade"), temp_path("upgrade-dest"));
+        create_v2(&path);
+
+        let mut version = 0;
+        assert_eq!(
+            redb_upgrade_database(
+                c_path(&path).as_ptr(),
+                c_path(&dest_path).as_ptr(),
+                &mut version
+            ),
+            REDB_OK
+        );
+        assert_eq!(version, 2);
+        assert_eq!(read_entry(&dest_path), "v");
+        // the source is left in the old format
+        assert!(redb::Database::open(&path).is_err());
+
+        assert_eq!(
+            redb_upgrade_database(c_path(&path).as_ptr(), ptr::null(), &mut version),
+            REDB_OK
+        );
+        assert_eq!(version, 2);
+        assert_eq!(read_entry(&path), "v");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&dest_path).unwrap();
+    }
+
+    #[test]
+    fn leaves_no_copy_of_files_it_cannot_upgrade() {
+        let (path, dest_path) = (temp_path("upgrade-v1"), temp_path("upgrade-v1-dest"));
+        create_v2(&path);
+        // the format version leads the first transaction slot of the header
+        let mut data = std::fs::read(&path).unwrap();
+        data[64] = 1;
+        std::fs::write(&path, data).unwrap();
+
+        let mut version = 0;
+        assert_eq!(
+            redb_upgrade_database(
+                c_path(&path).as_ptr(),
+                c_path(&dest_path).as_ptr(),
+                &mut version
+            ),
+            REDB_ERROR_UPGRADE_REQUIRED
+        );
+        assert_eq!(version, 1);
+        assert!(!dest_path.exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
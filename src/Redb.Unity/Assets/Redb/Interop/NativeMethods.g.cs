@@ -35,20 +35,174 @@ namespace Redb.Interop
         public const int REDB_ERROR_TABLE_ALREADY_OPEN = 27;
         public const int REDB_ERROR_READ_TRANSACTION_STILL_IN_USE = 41;
         public const int REDB_ERROR_INVALID_SAVEPOINT = 51;
+        public const int REDB_ERROR_UPGRADE = 61;
         public const int REDB_ERROR_KEY_NOT_FOUND = 100;
-
-
-
-
+        public const int REDB_ERROR_INVALID_DUMP = 101;
+        public const int REDB_ERROR_UNSUPPORTED_TYPE = 102;
+        public const int REDB_ERROR_INVALID_ENCRYPTION_KEY = 103;
+        public const int REDB_ERROR_CODEC = 104;
+        public const int REDB_ERROR_READ_ONLY = 105;
+        public const int REDB_ERROR_INVALID_PATH = 106;
+        public const int REDB_ERROR_NOT_FOUND = 107;
+        public const int REDB_ERROR_PERMISSION_DENIED = 108;
+        public const int REDB_ERROR_DISK_FULL = 109;
+        public const int REDB_ERROR_READ_ONLY_FILESYSTEM = 110;
+        public const int REDB_ERROR_INVALID_ENCODING = 111;
+        public const int REDB_ERROR_SCHEMA_MISMATCH = 112;
+        public const int REDB_ERROR_INVALID_MIGRATION = 113;
+        public const int REDB_ERROR_INVALID_HANDLE = 114;
+        public const int REDB_ERROR_SCHEMA_VERSION_TOO_NEW = 115;
+        public const int REDB_ERROR_CORRUPTED = 116;
+        public const int REDB_ERROR_RESERVED_TABLE_NAME = 117;
+        public const int REDB_ERROR_NOT_ENCRYPTED = 118;
+        public const byte REDB_FILE_FORMAT_VERSION = 3;
+
+
+
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
         [DllImport(__DllName, EntryPoint = "redb_create_database", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_create_database(byte* path, redb_database_options* options, void** @out);
 
+        /// <summary>
+        ///  Like `redb_create_database`, with the path given as `path_len` bytes of UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_create_database_utf8", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_create_database_utf8(byte* path, nuint path_len, redb_database_options* options, void** @out);
+
+        /// <summary>
+        ///  Like `redb_create_database`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_create_database_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_create_database_utf16(ushort* path, nuint path_len, redb_database_options* options, void** @out);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
         [DllImport(__DllName, EntryPoint = "redb_open_database", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_open_database(byte* path, void** @out);
 
+        /// <summary>
+        ///  Like `redb_open_database`, with the path given as `path_len` bytes of UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_open_database_utf8", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_open_database_utf8(byte* path, nuint path_len, void** @out);
+
+        /// <summary>
+        ///  Like `redb_open_database`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_open_database_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_open_database_utf16(ushort* path, nuint path_len, void** @out);
+
+        /// <summary>
+        ///  Reports whether the database file at `path` is currently locked by an open database, in this
+        ///  or another process. Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_is_file_locked", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_is_file_locked(byte* path, bool* out_locked);
+
+        /// <summary>
+        ///  Like `redb_is_file_locked`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_is_file_locked_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_is_file_locked_utf16(ushort* path, nuint path_len, bool* out_locked);
+
+        /// <summary>
+        ///  Opens an in-memory database initialized with a copy of the database image in `data`.
+        ///
+        ///  Only `cache_size` and `read_only` are taken from `options`, which may be null. An empty image
+        ///  creates an empty database.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_create_database_from_bytes", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_create_database_from_bytes(byte* data, nuint len, redb_database_options* options, void** @out);
+
+        /// <summary>
+        ///  Serializes a compacted copy of the committed contents of `db` into a database image that
+        ///  `redb_create_database_from_bytes` can open. The image is freed with `redb_free_blob`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_database_to_bytes", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_database_to_bytes(void* db, redb_blob* @out);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` or `dest_path` is not valid UTF-8. `dest_path`
+        ///  may be null to upgrade `path` in place.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_upgrade_database", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_upgrade_database(byte* path, byte* dest_path, byte* out_version);
+
+        /// <summary>
+        ///  Like `redb_upgrade_database`, with the paths given as UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_upgrade_database_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_upgrade_database_utf16(ushort* path, nuint path_len, ushort* dest_path, nuint dest_path_len, byte* out_version);
+
         [DllImport(__DllName, EntryPoint = "redb_compact_database", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_compact_database(void* db);
 
+        [DllImport(__DllName, EntryPoint = "redb_database_cache_stats", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_database_cache_stats(void* db, redb_cache_stats* @out);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_backup_to", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_backup_to(void* db, byte* path, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Like `redb_backup_to`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_backup_to_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_backup_to_utf16(void* db, ushort* path, nuint path_len, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_vacuum_into", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_vacuum_into(void* db, byte* path, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Like `redb_vacuum_into`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_vacuum_into_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_vacuum_into_utf16(void* db, ushort* path, nuint path_len, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Replaces the database file at `path` with the one written by `redb_vacuum_into`.
+        ///
+        ///  `*db` is closed before the swap and replaced by a handle to the swapped-in file, opened with the
+        ///  same backend and options as `*db`, so every transaction on it must have been freed first. If
+        ///  the rename fails or the swapped-in file does not open, both files are put back, the original
+        ///  is reopened in `*db` and the error code of the failed step returned. Returns
+        ///  `REDB_ERROR_INVALID_ENCODING` if either path is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_vacuum_swap", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_vacuum_swap(void** db, byte* path, byte* vacuum_path);
+
+        /// <summary>
+        ///  Like `redb_vacuum_swap`, with the paths given as UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_vacuum_swap_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_vacuum_swap_utf16(void** db, ushort* path, nuint path_len, ushort* vacuum_path, nuint vacuum_path_len);
+
+        /// <summary>
+        ///  Writes a compacted copy of the committed contents of `db` to a new database file at `path`,
+        ///  typically to save an `InMemory` database.
+        ///
+        ///  The copy is written to a temporary file next to `path` and renamed over it once complete, so
+        ///  `path` never holds a partially written database. `db` stays open on its original backend.
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_save_as", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_save_as(void* db, byte* path, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Like `redb_save_as`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_save_as_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_save_as_utf16(void* db, ushort* path, nuint path_len, redb_copy_stats* out_stats);
+
         [DllImport(__DllName, EntryPoint = "redb_free_database", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern void redb_free_database(void* db);
 
@@ -70,15 +224,32 @@ namespace Redb.Interop
         [DllImport(__DllName, EntryPoint = "redb_free_string", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern void redb_free_string(byte* s);
 
-        [DllImport(__DllName, EntryPoint = "redb_free", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        public static extern void redb_free(void* ptr);
-
+        /// <summary>
+        ///  Frees a blob written by the library and resets it to empty, so freeing it again does nothing.
+        /// </summary>
         [DllImport(__DllName, EntryPoint = "redb_free_blob", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        public static extern void redb_free_blob(byte* blob);
+        public static extern void redb_free_blob(redb_blob* blob);
+
+        /// <summary>
+        ///  Frees an array written by the library and resets it to empty, so freeing it again does nothing.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_free_u64_array", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void redb_free_u64_array(redb_u64_array* array);
 
         [DllImport(__DllName, EntryPoint = "redb_begin_write", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_begin_write(void* db, void** @out);
 
+        /// <summary>
+        ///  Applies the steps of `migrations` newer than the database schema version, in order, in a single
+        ///  write transaction, then records the last step's version. Steps must have strictly increasing
+        ///  versions. If a step fails its error code is returned, the transaction is aborted, and tables
+        ///  the step left open are closed. Without any steps the database is left as it is; otherwise
+        ///  `REDB_ERROR_SCHEMA_VERSION_TOO_NEW` is returned if it is already newer than the last step.
+        ///  `out_version` receives the schema version the database is at afterwards.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_migrate", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_migrate(void* db, redb_migration* migrations, nuint migration_count, uint* out_version);
+
         [DllImport(__DllName, EntryPoint = "redb_write_tx_set_durability", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_write_tx_set_durability(void* tx, redb_durability durability);
 
@@ -91,12 +262,77 @@ namespace Redb.Interop
         [DllImport(__DllName, EntryPoint = "redb_write_tx_open_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_write_tx_open_table(void* tx, byte* name, void** @out);
 
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_open_table_with_codec", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_open_table_with_codec(void* tx, byte* name, redb_table_codec_options* options, void** @out);
+
+        /// <summary>
+        ///  Opens a table whose key and value types are given by descriptors. The handle is used with the
+        ///  same exports as any other table, with keys and values in redb's encoding for those types.
+        ///  The first write open records the redb type names and the `redb` encoding as the table's schema;
+        ///  later opens with other types fail with `REDB_ERROR_SCHEMA_MISMATCH`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_open_typed_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_open_typed_table(void* tx, byte* name, redb_type_descriptor* key_type, redb_type_descriptor* value_type, void** @out);
+
+        /// <summary>
+        ///  Registers the ordering of keys of the `Custom` type named `type_name`. The comparator must
+        ///  order keys exactly as the type's Rust `Key::compare` does if the table is shared with Rust
+        ///  programs, and must never change for a name once a table uses it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_register_key_comparator", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_register_key_comparator(byte* type_name, redb_key_comparator* comparator);
+
+        /// <summary>
+        ///  Removes the comparator registered for `type_name`. Tables already open keep using it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_unregister_key_comparator", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_unregister_key_comparator(byte* type_name);
+
+        /// <summary>
+        ///  Opens a table keyed by a redb integer type with `&amp;[u8]` values, matching a Rust
+        ///  `TableDefinition&lt;u64, &amp;[u8]&gt;` and so on. Keys sort numerically; use the `_u64`, `_i64`, `_u32`
+        ///  and `_i32` insert, get and range exports with it.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_open_numeric_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_open_numeric_table(void* tx, byte* name, redb_numeric_key key_type, void** @out);
+
+        /// <summary>
+        ///  Opens a table keyed by a tuple of `component_count` component types with `&amp;[u8]` values,
+        ///  matching a Rust `TableDefinition&lt;(u32, i64, [u8; 16]), &amp;[u8]&gt;` and so on. Build keys with
+        ///  `redb_encode_composite_key` and scan everything under a leading set of components with
+        ///  `redb_range_prefix`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_open_composite_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_open_composite_table(void* tx, byte* name, redb_type_descriptor* components, nuint component_count, void** @out);
+
         [DllImport(__DllName, EntryPoint = "redb_write_tx_delete_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_write_tx_delete_table(void* tx, byte* name);
 
         [DllImport(__DllName, EntryPoint = "redb_write_tx_rename_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_write_tx_rename_table(void* tx, byte* old_name, byte* new_name);
 
+        /// <summary>
+        ///  Records the key type, value type, encoding and version of table `name` in the database's
+        ///  reserved `$redb.schema` table, replacing any earlier record. Deleting or renaming the table
+        ///  through `redb_write_tx_delete_table` and `redb_write_tx_rename_table` updates the record.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_set_table_schema", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_set_table_schema(void* tx, byte* name, redb_table_schema* schema);
+
+        /// <summary>
+        ///  Reads the schema recorded for table `name`, or returns `REDB_ERROR_KEY_NOT_FOUND`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_get_table_schema", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_get_table_schema(void* tx, byte* name, redb_table_schema* @out);
+
+        /// <summary>
+        ///  Checks table `name` against the schema recorded for it. Null strings in `expected` are not
+        ///  checked. Returns `REDB_ERROR_KEY_NOT_FOUND` when nothing is recorded and
+        ///  `REDB_ERROR_SCHEMA_MISMATCH` when the record differs.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_validate_table_schema", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_validate_table_schema(void* tx, byte* name, redb_table_schema* expected);
+
         [DllImport(__DllName, EntryPoint = "redb_write_tx_abort", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_write_tx_abort(void* tx);
 
@@ -119,7 +355,19 @@ namespace Redb.Interop
         public static extern int redb_write_tx_delete_persistent_savepoint(void* tx, ulong id, bool* @out);
 
         [DllImport(__DllName, EntryPoint = "redb_write_tx_lists_persistent_savepoint", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        public static extern int redb_write_tx_lists_persistent_savepoint(void* tx, ulong** @out, nuint* count);
+        public static extern int redb_write_tx_lists_persistent_savepoint(void* tx, redb_u64_array* @out);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` or a table name is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_import", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_import(void* tx, byte* path, byte** table_names, nuint table_count, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Like `redb_write_tx_import`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_write_tx_import_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_write_tx_import_utf16(void* tx, ushort* path, nuint path_len, byte** table_names, nuint table_count, redb_copy_stats* out_stats);
 
         [DllImport(__DllName, EntryPoint = "redb_insert", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_insert(void* table, byte* key, nuint key_len, byte* value, nuint value_len);
@@ -130,8 +378,50 @@ namespace Redb.Interop
         [DllImport(__DllName, EntryPoint = "redb_read_tx_open_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_read_tx_open_table(void* tx, byte* name, void** @out);
 
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_open_table_with_codec", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_open_table_with_codec(void* tx, byte* name, redb_table_codec_options* options, void** @out);
+
+        /// <summary>
+        ///  Read-only counterpart of `redb_write_tx_open_typed_table`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_open_typed_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_open_typed_table(void* tx, byte* name, redb_type_descriptor* key_type, redb_type_descriptor* value_type, void** @out);
+
+        /// <summary>
+        ///  Read-only counterpart of `redb_write_tx_open_numeric_table`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_open_numeric_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_open_numeric_table(void* tx, byte* name, redb_numeric_key key_type, void** @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_open_composite_table", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_open_composite_table(void* tx, byte* name, redb_type_descriptor* components, nuint component_count, void** @out);
+
+        /// <summary>
+        ///  Reads the database schema version recorded by `redb_migrate`, 0 if it has never run.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_get_schema_version", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_get_schema_version(void* tx, uint* out_version);
+
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_get_table_schema", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_get_table_schema(void* tx, byte* name, redb_table_schema* @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_validate_table_schema", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_validate_table_schema(void* tx, byte* name, redb_table_schema* expected);
+
+        /// <summary>
+        ///  Returns `REDB_ERROR_INVALID_ENCODING` if `path` or a table name is not valid UTF-8.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_export", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_export(void* tx, byte* path, byte** table_names, nuint table_count, redb_copy_stats* out_stats);
+
+        /// <summary>
+        ///  Like `redb_read_tx_export`, with the path given as `path_len` UTF-16 code units.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_read_tx_export_utf16", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_read_tx_export_utf16(void* tx, ushort* path, nuint path_len, byte** table_names, nuint table_count, redb_copy_stats* out_stats);
+
         [DllImport(__DllName, EntryPoint = "redb_get", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        public static extern int redb_get(void* table, byte* key, nuint key_len, byte** blob, nuint* count);
+        public static extern int redb_get(void* table, byte* key, nuint key_len, redb_blob* @out);
 
         [DllImport(__DllName, EntryPoint = "redb_table_len", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_table_len(void* table, ulong* @out);
@@ -142,8 +432,66 @@ namespace Redb.Interop
         [DllImport(__DllName, EntryPoint = "redb_range", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern int redb_range(void* table, byte* start_key, nuint start_key_len, byte* end_key, nuint end_key_len, void** @out);
 
+        [DllImport(__DllName, EntryPoint = "redb_insert_u64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_insert_u64(void* table, ulong key, byte* value, nuint value_len);
+
+        [DllImport(__DllName, EntryPoint = "redb_insert_i64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_insert_i64(void* table, long key, byte* value, nuint value_len);
+
+        [DllImport(__DllName, EntryPoint = "redb_insert_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_insert_u32(void* table, uint key, byte* value, nuint value_len);
+
+        [DllImport(__DllName, EntryPoint = "redb_insert_i32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_insert_i32(void* table, int key, byte* value, nuint value_len);
+
+        [DllImport(__DllName, EntryPoint = "redb_get_u64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_get_u64(void* table, ulong key, redb_blob* @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_get_i64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_get_i64(void* table, long key, redb_blob* @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_get_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_get_u32(void* table, uint key, redb_blob* @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_get_i32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_get_i32(void* table, int key, redb_blob* @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_range_u64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_range_u64(void* table, ulong* start, ulong* end, void** @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_range_i64", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_range_i64(void* table, long* start, long* end, void** @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_range_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_range_u32(void* table, uint* start, uint* end, void** @out);
+
+        [DllImport(__DllName, EntryPoint = "redb_range_i32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_range_i32(void* table, int* start, int* end, void** @out);
+
+        /// <summary>
+        ///  Encodes a composite key from the redb encodings of its `component_count` components, for use
+        ///  with `redb_insert`, `redb_get` and `redb_range` on a composite table. Free it with
+        ///  `redb_free_blob`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_encode_composite_key", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_encode_composite_key(redb_type_descriptor* components, nuint component_count, redb_key_part* parts, redb_blob* @out);
+
+        /// <summary>
+        ///  Splits a composite key into its `component_count` components. The parts written to
+        ///  `out_parts` point into `key` and are only valid as long as it is.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_decode_composite_key", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_decode_composite_key(redb_type_descriptor* components, nuint component_count, byte* key, nuint key_len, redb_key_part* out_parts);
+
+        /// <summary>
+        ///  Iterates the entries of a composite table whose keys start with the `part_count` leading
+        ///  components in `parts`, in key order. Zero parts iterates the whole table.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "redb_range_prefix", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int redb_range_prefix(void* table, redb_key_part* parts, nuint part_count, void** @out);
+
         [DllImport(__DllName, EntryPoint = "redb_iter_next", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
-        public static extern int redb_iter_next(void* iter, byte** key_blob, nuint* key_len, byte** value_blob, nuint* value_len);
+        public static extern int redb_iter_next(void* iter, redb_blob* key_blob, redb_blob* value_blob);
 
         [DllImport(__DllName, EntryPoint = "redb_free_iter", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern void redb_free_iter(void* iter);
@@ -156,6 +504,105 @@ namespace Redb.Interop
     {
         public nuint cache_size;
         public redb_backend backend;
+        public redb_storage_backend* storage;
+        public byte* encryption_key;
+        [MarshalAs(UnmanagedType.U1)] public bool read_only;
+        public uint lock_timeout_ms;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_storage_backend
+    {
+        public void* user_data;
+        public delegate* unmanaged[Cdecl]<void*, ulong*, int> len;
+        public delegate* unmanaged[Cdecl]<void*, ulong, byte*, nuint, int> read;
+        public delegate* unmanaged[Cdecl]<void*, ulong, byte*, nuint, int> write;
+        public delegate* unmanaged[Cdecl]<void*, ulong, int> set_len;
+        public delegate* unmanaged[Cdecl]<void*, int> sync_data;
+        public delegate* unmanaged[Cdecl]<void*, int> close;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_cache_stats
+    {
+        public ulong evictions;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_copy_stats
+    {
+        public ulong tables;
+        public ulong entries;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_table_codec_options
+    {
+        public redb_value_codec codec;
+        public int level;
+        public byte* dictionary;
+        public nuint dictionary_len;
+    }
+
+    /// <summary>
+    ///  Describes a redb key or value type, so tables written by Rust programs can be opened with the
+    ///  same types. Keys and values of such tables are passed in redb's binary encoding for the type.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_type_descriptor
+    {
+        public redb_type_kind kind;
+        public redb_type_descriptor* elements;
+        public nuint element_count;
+        public nuint array_len;
+        public byte* name;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_key_comparator
+    {
+        public void* user_data;
+        public delegate* unmanaged[Cdecl]<void*, byte*, nuint, byte*, nuint, int> compare;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_key_part
+    {
+        public byte* data;
+        public nuint len;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_blob
+    {
+        public byte* data;
+        public nuint len;
+        public nuint capacity;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_u64_array
+    {
+        public ulong* data;
+        public nuint len;
+        public nuint capacity;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_table_schema
+    {
+        public byte* key_type;
+        public byte* value_type;
+        public byte* encoding;
+        public uint version;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct redb_migration
+    {
+        public uint version;
+        public void* user_data;
+        public delegate* unmanaged[Cdecl]<void*, void*, int> migrate;
     }
 
 
@@ -163,6 +610,49 @@ namespace Redb.Interop
     {
         File,
         InMemory,
+        Callback,
+        EncryptedFile,
+        CompressedFile,
+        Mmap,
+    }
+
+    public enum redb_value_codec : uint
+    {
+        None,
+        Lz4,
+        Zstd,
+    }
+
+    public enum redb_type_kind : uint
+    {
+        Unit,
+        Bool,
+        U8,
+        U16,
+        U32,
+        U64,
+        U128,
+        I8,
+        I16,
+        I32,
+        I64,
+        I128,
+        F32,
+        F64,
+        Str,
+        Bytes,
+        Array,
+        Tuple,
+        Custom,
+        Descending,
+    }
+
+    public enum redb_numeric_key : uint
+    {
+        U64,
+        I64,
+        U32,
+        I32,
     }
 
     public enum redb_durability : uint
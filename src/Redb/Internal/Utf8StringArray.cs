@@ -0,0 +1,36 @@
+using System.Runtime.InteropServices;
+
+namespace Redb.Internal;
+
+internal unsafe struct Utf8StringArray : IDisposable
+{
+    byte** pointers;
+    int length;
+
+    public Utf8StringArray(ReadOnlySpan<string> strings)
+    {
+        pointers = (byte**)Marshal.AllocHGlobal(sizeof(byte*) * Math.Max(strings.Length, 1));
+        length = strings.Length;
+
+        for (int i = 0; i < strings.Length; i++)
+        {
+            pointers[i] = (byte*)Marshal.StringToCoTaskMemUTF8(strings[i]);
+        }
+    }
+
+    public readonly byte** Pointers => pointers;
+    public readonly int Length => length;
+
+    public void Dispose()
+    {
+        if (pointers != null)
+        {
+            for (int i = 0; i < length; i++)
+            {
+                Marshal.FreeCoTaskMem((nint)pointers[i]);
+            }
+            Marshal.FreeHGlobal((nint)pointers);
+        }
+        pointers = null;
+    }
+}
@@ -45,12 +45,11 @@ public unsafe sealed class ReadOnlyTable : IDisposable
 
         fixed (byte* keyPtr = key)
         {
-            byte* ptr;
-            nuint written;
-            var code = NativeMethods.redb_get(table, keyPtr, (nuint)key.Length, &ptr, &written);
+            redb_blob value;
+            var code = NativeMethods.redb_get(table, keyPtr, (nuint)key.Length, &value);
             if (code == NativeMethods.REDB_OK)
             {
-                blob = new RedbBlob(ptr, written);
+                blob = new RedbBlob(value);
                 return true;
             }
             else
@@ -139,16 +138,14 @@ public unsafe sealed class ReadOnlyTable : IDisposable
 
             if (iter == null) return false;
 
-            byte* keyPtr;
-            nuint keyLen;
-            byte* valuePtr;
-            nuint valueLen;
+            redb_blob key;
+            redb_blob value;
 
-            var code = NativeMethods.redb_iter_next(iter, &keyPtr, &keyLen, &valuePtr, &valueLen);
+            var code = NativeMethods.redb_iter_next(iter, &key, &value);
             if (code == NativeMethods.REDB_OK)
             {
-                keyBlob = new RedbBlob(keyPtr, keyLen);
-                valueBlob = new RedbBlob(valuePtr, valueLen);
+                keyBlob = new RedbBlob(key);
+                valueBlob = new RedbBlob(value);
 
                 current = new ReadOnlySpanKeyValuePair
                 {
@@ -125,6 +125,36 @@ public unsafe sealed class ReadTransaction : IDisposable
         return table;
     }
 
+    public RedbCopyStats Export(ReadOnlySpan<char> path)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_read_tx_export_utf16(tx, (ushort*)pathPtr, (nuint)path.Length, null, 0, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to export tables");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
+    public RedbCopyStats Export(ReadOnlySpan<char> path, ReadOnlySpan<string> tableNames)
+    {
+        ThrowIfDisposed();
+
+        using var tableNamesBuffer = new Utf8StringArray(tableNames);
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_read_tx_export_utf16(tx, (ushort*)pathPtr, (nuint)path.Length, tableNamesBuffer.Pointers, (nuint)tableNamesBuffer.Length, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to export tables");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
     [MethodImpl(MethodImplOptions.AggressiveInlining)]
     void ThrowIfDisposed()
     {
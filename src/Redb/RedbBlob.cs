@@ -6,33 +6,33 @@ namespace Redb;
 
 public unsafe struct RedbBlob : IDisposable
 {
-    byte* ptr;
-    nuint length;
+    redb_blob blob;
 
-    internal RedbBlob(byte* ptr, nuint length)
+    internal RedbBlob(redb_blob blob)
     {
-        this.ptr = ptr;
-        this.length = length;
+        this.blob = blob;
     }
 
     public readonly ReadOnlySpan<byte> AsSpan()
     {
         ThrowIfDisposed();
-        return new ReadOnlySpan<byte>(ptr, (int)length);
+        return new ReadOnlySpan<byte>(blob.data, (int)blob.len);
     }
 
     public void Dispose()
     {
-        if (ptr != null)
+        if (blob.data != null)
         {
-            NativeMethods.redb_free_blob(ptr);
-            ptr = null;
+            fixed (redb_blob* p = &blob)
+            {
+                NativeMethods.redb_free_blob(p);
+            }
         }
     }
 
     [MethodImpl(MethodImplOptions.AggressiveInlining)]
     readonly void ThrowIfDisposed()
     {
-        ThrowHelper.ThrowIfDisposed(ptr == null, nameof(RedbBlob));
+        ThrowHelper.ThrowIfDisposed(blob.data == null, nameof(RedbBlob));
     }
 }
@@ -0,0 +1,3 @@
+namespace Redb;
+
+public readonly record struct RedbCacheStats(ulong Evictions);
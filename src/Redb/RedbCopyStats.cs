@@ -0,0 +1,3 @@
+namespace Redb;
+
+public readonly record struct RedbCopyStats(ulong Tables, ulong Entries);
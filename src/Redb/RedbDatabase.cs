@@ -15,31 +15,101 @@ public unsafe sealed class RedbDatabase : IDisposable
         this.db = db;
     }
 
-    [MethodImpl(MethodImplOptions.AggressiveInlining)]
     public static RedbDatabase Create(ReadOnlySpan<byte> utf8Path, RedbDatabaseOptions? options = null)
     {
-        var pathBuffer = new NullTerminatedUtf8String(utf8Path);
-        return CreateCore(pathBuffer, options);
+        void* db;
+        var opts = options?.ToNative() ?? default;
+
+        fixed (byte* keyPtr = options?.EncryptionKey)
+        fixed (byte* pathPtr = utf8Path)
+        {
+            opts.encryption_key = keyPtr;
+            int code = NativeMethods.redb_create_database_utf8(pathPtr, (nuint)utf8Path.Length, options == null ? null : &opts, &db);
+            return CreateResult(db, code);
+        }
     }
 
-    [MethodImpl(MethodImplOptions.AggressiveInlining)]
     public static RedbDatabase Create(ReadOnlySpan<char> path, RedbDatabaseOptions? options = null)
     {
-        var pathBuffer = new NullTerminatedUtf8String(path);
-        return CreateCore(pathBuffer, options);
+        void* db;
+        var opts = options?.ToNative() ?? default;
+
+        fixed (byte* keyPtr = options?.EncryptionKey)
+        fixed (char* pathPtr = path)
+        {
+            opts.encryption_key = keyPtr;
+            int code = NativeMethods.redb_create_database_utf16((ushort*)pathPtr, (nuint)path.Length, options == null ? null : &opts, &db);
+            return CreateResult(db, code);
+        }
+    }
+
+    static RedbDatabase CreateResult(void* db, int code)
+    {
+        if (code != 0)
+        {
+            throw new RedbDatabaseException("Failed to create database", code);
+        }
+
+        Debug.Assert(db != null);
+        return new RedbDatabase(db);
+    }
+
+    public static RedbDatabase Open(ReadOnlySpan<byte> utf8Path)
+    {
+        void* db;
+
+        fixed (byte* pathPtr = utf8Path)
+        {
+            int code = NativeMethods.redb_open_database_utf8(pathPtr, (nuint)utf8Path.Length, &db);
+            return OpenResult(db, code);
+        }
+    }
+
+    public static RedbDatabase Open(ReadOnlySpan<char> path)
+    {
+        void* db;
+
+        fixed (char* pathPtr = path)
+        {
+            int code = NativeMethods.redb_open_database_utf16((ushort*)pathPtr, (nuint)path.Length, &db);
+            return OpenResult(db, code);
+        }
     }
 
-    static RedbDatabase CreateCore(NullTerminatedUtf8String path, RedbDatabaseOptions? options = null)
+    static RedbDatabase OpenResult(void* db, int code)
+    {
+        if (code != 0)
+        {
+            throw new RedbDatabaseException("Failed to open database", code);
+        }
+
+        Debug.Assert(db != null);
+        return new RedbDatabase(db);
+    }
+
+    public static bool IsFileLocked(ReadOnlySpan<char> path)
+    {
+        fixed (char* pathPtr = path)
+        {
+            bool locked;
+            var code = NativeMethods.redb_is_file_locked_utf16((ushort*)pathPtr, (nuint)path.Length, &locked);
+            ThrowHelper.ThrowIfError(code, "Failed to query file lock");
+
+            return locked;
+        }
+    }
+
+    public static RedbDatabase CreateFromBytes(ReadOnlySpan<byte> image, RedbDatabaseOptions? options = null)
     {
         void* db;
         var opts = options?.ToNative() ?? default;
 
-        fixed (byte* pathPtr = path)
+        fixed (byte* imagePtr = image)
         {
-            int code = NativeMethods.redb_create_database(pathPtr, options == null ? null : &opts, &db);
+            int code = NativeMethods.redb_create_database_from_bytes(imagePtr, (nuint)image.Length, options == null ? null : &opts, &db);
             if (code != 0)
             {
-                throw new RedbDatabaseException("Failed to create database", code);
+                throw new RedbDatabaseException("Failed to create database from bytes", code);
             }
 
             Debug.Assert(db != null);
@@ -47,34 +117,40 @@ public unsafe sealed class RedbDatabase : IDisposable
         }
     }
 
-    [MethodImpl(MethodImplOptions.AggressiveInlining)]
-    public static RedbDatabase Open(ReadOnlySpan<byte> utf8Path)
+    public static byte Upgrade(ReadOnlySpan<char> path)
     {
-        var pathBuffer = new NullTerminatedUtf8String(utf8Path);
-        return OpenCore(pathBuffer);
-    }
+        fixed (char* pathPtr = path)
+        {
+            byte version = 0;
+            int code = NativeMethods.redb_upgrade_database_utf16((ushort*)pathPtr, (nuint)path.Length, null, 0, &version);
+            if (code != 0)
+            {
+                throw new RedbDatabaseException($"Failed to upgrade database (file format version {version})", code);
+            }
 
-    [MethodImpl(MethodImplOptions.AggressiveInlining)]
-    public static RedbDatabase Open(ReadOnlySpan<char> path)
-    {
-        var pathBuffer = new NullTerminatedUtf8String(path);
-        return OpenCore(pathBuffer);
+            return version;
+        }
     }
 
-    static RedbDatabase OpenCore(NullTerminatedUtf8String path)
+    public static byte Upgrade(ReadOnlySpan<char> path, ReadOnlySpan<char> destinationPath)
     {
-        void* db;
+        // an empty span pins to null, which the native side takes as an in-place upgrade
+        if (destinationPath.IsEmpty)
+        {
+            throw new ArgumentException("The destination path is empty; use Upgrade(path) to upgrade in place.", nameof(destinationPath));
+        }
 
-        fixed (byte* pathPtr = path)
+        fixed (char* pathPtr = path)
+        fixed (char* destinationPathPtr = destinationPath)
         {
-            int code = NativeMethods.redb_open_database(pathPtr, &db);
+            byte version = 0;
+            int code = NativeMethods.redb_upgrade_database_utf16((ushort*)pathPtr, (nuint)path.Length, (ushort*)destinationPathPtr, (nuint)destinationPath.Length, &version);
             if (code != 0)
             {
-                throw new RedbDatabaseException("Failed to open database", code);
+                throw new RedbDatabaseException($"Failed to upgrade database (file format version {version})", code);
             }
 
-            Debug.Assert(db != null);
-            return new RedbDatabase(db);
+            return version;
         }
     }
 
@@ -89,6 +165,83 @@ public unsafe sealed class RedbDatabase : IDisposable
         }
     }
 
+    public RedbCopyStats BackupTo(ReadOnlySpan<char> path)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_backup_to_utf16(db, (ushort*)pathPtr, (nuint)path.Length, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to back up database");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
+    public RedbCopyStats VacuumInto(ReadOnlySpan<char> path)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_vacuum_into_utf16(db, (ushort*)pathPtr, (nuint)path.Length, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to vacuum database");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
+    public RedbBlob ToBytes()
+    {
+        ThrowIfDisposed();
+
+        redb_blob image;
+        var code = NativeMethods.redb_database_to_bytes(db, &image);
+        ThrowHelper.ThrowIfError(code, "Failed to serialize database");
+
+        return new RedbBlob(image);
+    }
+
+    public RedbCopyStats SaveAs(ReadOnlySpan<char> path)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_save_as_utf16(db, (ushort*)pathPtr, (nuint)path.Length, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to save database");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
+    public void SwapWithVacuum(ReadOnlySpan<char> path, ReadOnlySpan<char> vacuumPath)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        fixed (char* vacuumPathPtr = vacuumPath)
+        fixed (void** dbPtr = &db)
+        {
+            var code = NativeMethods.redb_vacuum_swap_utf16(dbPtr, (ushort*)pathPtr, (nuint)path.Length, (ushort*)vacuumPathPtr, (nuint)vacuumPath.Length);
+            ThrowHelper.ThrowIfError(code, "Failed to swap in vacuumed database");
+        }
+    }
+
+    public RedbCacheStats GetCacheStats()
+    {
+        ThrowIfDisposed();
+
+        redb_cache_stats stats;
+        var code = NativeMethods.redb_database_cache_stats(db, &stats);
+        ThrowHelper.ThrowIfError(code, "Failed to get cache stats");
+
+        return new RedbCacheStats(stats.evictions);
+    }
+
     public WriteTransaction BeginWrite()
     {
         ThrowIfDisposed();
@@ -6,6 +6,9 @@ public enum RedbBackend : int
 {
     File = 0,
     InMemory = 1,
+    EncryptedFile = 3,
+    CompressedFile = 4,
+    Mmap = 5,
 }
 
 public record RedbDatabaseOptions
@@ -14,13 +17,24 @@ public record RedbDatabaseOptions
 
     public nuint CacheSize { get; init; } = 64 * 1024 * 1024;
     public RedbBackend Backend { get; init; } = RedbBackend.File;
+    public bool ReadOnly { get; init; }
+    public TimeSpan LockTimeout { get; init; } = TimeSpan.Zero;
+    // 32 bytes, required for RedbBackend.EncryptedFile; pinned by the caller of ToNative
+    public byte[]? EncryptionKey { get; init; }
 
     internal redb_database_options ToNative()
     {
+        if (Backend == RedbBackend.EncryptedFile && EncryptionKey?.Length != 32)
+        {
+            throw new ArgumentException("The EncryptedFile backend needs a 32 byte EncryptionKey.", nameof(EncryptionKey));
+        }
+
         return new redb_database_options
         {
             cache_size = CacheSize,
             backend = (redb_backend)(int)Backend,
+            read_only = ReadOnly,
+            lock_timeout_ms = (uint)Math.Clamp(LockTimeout.TotalMilliseconds, 0, uint.MaxValue),
         };
     }
 }
\ No newline at end of file
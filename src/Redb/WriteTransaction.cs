@@ -179,6 +179,36 @@ public unsafe sealed class WriteTransaction : IDisposable
         }
     }
 
+    public RedbCopyStats Import(ReadOnlySpan<char> path)
+    {
+        ThrowIfDisposed();
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_write_tx_import_utf16(tx, (ushort*)pathPtr, (nuint)path.Length, null, 0, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to import tables");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
+    public RedbCopyStats Import(ReadOnlySpan<char> path, ReadOnlySpan<string> tableNames)
+    {
+        ThrowIfDisposed();
+
+        using var tableNamesBuffer = new Utf8StringArray(tableNames);
+
+        fixed (char* pathPtr = path)
+        {
+            redb_copy_stats stats;
+            var code = NativeMethods.redb_write_tx_import_utf16(tx, (ushort*)pathPtr, (nuint)path.Length, tableNamesBuffer.Pointers, (nuint)tableNamesBuffer.Length, &stats);
+            ThrowHelper.ThrowIfError(code, "Failed to import tables");
+
+            return new RedbCopyStats(stats.tables, stats.entries);
+        }
+    }
+
     public void Commit()
     {
         ThrowIfDisposed();
@@ -239,9 +269,8 @@ public unsafe sealed class WriteTransaction : IDisposable
     {
         ThrowIfDisposed();
 
-        ulong* idsPtr;
-        nuint count;
-        var code = NativeMethods.redb_write_tx_lists_persistent_savepoint(tx, &idsPtr, &count);
+        redb_u64_array ids;
+        var code = NativeMethods.redb_write_tx_lists_persistent_savepoint(tx, &ids);
         if (code != 0)
         {
             throw new RedbDatabaseException("Failed to list persistent savepoints", code);
@@ -249,12 +278,12 @@ public unsafe sealed class WriteTransaction : IDisposable
 
         try
         {
-            var idsSpan = new ReadOnlySpan<ulong>(idsPtr, (int)count);
+            var idsSpan = new ReadOnlySpan<ulong>(ids.data, (int)ids.len);
             return idsSpan.ToArray();
         }
         finally
         {
-            NativeMethods.redb_free(idsPtr);
+            NativeMethods.redb_free_u64_array(&ids);
         }
     }
 